@@ -0,0 +1,104 @@
+//! Which palette algorithm a request runs: iterative k-means, one of the
+//! single-pass quantizers, or density-based DBSCAN. Only the quantizers share
+//! the [`Quantizer`](crate::quantize::Quantizer) interface; the other two are
+//! configured through `KMeansConfig` and `DbscanConfig`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::quantize::QuantizerKind;
+
+/// Palette algorithm selector for requests. On the wire and on the command
+/// line it is a single name (`kmeans`, `medianCut`, `octree`, `wu`, `dbscan`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum PaletteAlgorithm {
+    #[default]
+    Kmeans,
+    Quantizer(QuantizerKind),
+    /// Density-based clustering that picks the number of colors itself.
+    Dbscan,
+}
+
+impl PaletteAlgorithm {
+    pub const ALL: [PaletteAlgorithm; 5] = [
+        PaletteAlgorithm::Kmeans,
+        PaletteAlgorithm::Quantizer(QuantizerKind::MedianCut),
+        PaletteAlgorithm::Quantizer(QuantizerKind::Octree),
+        PaletteAlgorithm::Quantizer(QuantizerKind::Wu),
+        PaletteAlgorithm::Dbscan,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PaletteAlgorithm::Kmeans => "kmeans",
+            PaletteAlgorithm::Quantizer(kind) => kind.name(),
+            PaletteAlgorithm::Dbscan => "dbscan",
+        }
+    }
+}
+
+impl fmt::Display for PaletteAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for PaletteAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let wanted = s.trim().replace(['-', '_'], "").to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().to_ascii_lowercase() == wanted)
+            .ok_or_else(|| {
+                format!(
+                    "unknown algorithm '{s}' (expected kmeans, median-cut, octree, wu or dbscan)"
+                )
+            })
+    }
+}
+
+impl From<PaletteAlgorithm> for String {
+    fn from(algorithm: PaletteAlgorithm) -> Self {
+        algorithm.name().to_owned()
+    }
+}
+
+impl TryFrom<String> for PaletteAlgorithm {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algorithm_parses_cli_and_wire_names() {
+        assert_eq!(
+            "median-cut".parse(),
+            Ok(PaletteAlgorithm::Quantizer(QuantizerKind::MedianCut))
+        );
+        assert_eq!("kMeans".parse(), Ok(PaletteAlgorithm::Kmeans));
+        assert_eq!("dbscan".parse(), Ok(PaletteAlgorithm::Dbscan));
+        assert!("pca".parse::<PaletteAlgorithm>().is_err());
+
+        let wu: PaletteAlgorithm = serde_json::from_str("\"wu\"").unwrap();
+        assert_eq!(wu, PaletteAlgorithm::Quantizer(QuantizerKind::Wu));
+        for algorithm in PaletteAlgorithm::ALL {
+            let json = serde_json::to_string(&algorithm).unwrap();
+            assert_eq!(json, format!("\"{algorithm}\""));
+            assert_eq!(
+                serde_json::from_str::<PaletteAlgorithm>(&json).unwrap(),
+                algorithm
+            );
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Palette entries as the front-end sees them: centroids converted back to
+//! RGB and friends, empty clusters dropped, heaviest first.
+
+use serde::Serialize;
+
+use super::ClusterRegion;
+use crate::color::{self, ColorSpace, SpaceConversion};
+use crate::kmeans::KMeansResult;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RgbValue {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl From<[u8; 3]> for RgbValue {
+    fn from(rgb: [u8; 3]) -> Self {
+        Self {
+            r: rgb[0],
+            g: rgb[1],
+            b: rgb[2],
+        }
+    }
+}
+
+impl RgbValue {
+    pub fn to_array(self) -> [u8; 3] {
+        [self.r, self.g, self.b]
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterOut {
+    pub count: usize,
+    pub share: f64,
+    pub centroid_space: [f32; 3],
+    pub rgb: RgbValue,
+    pub hsv: [f32; 3],
+    /// CIE LCh(ab) of `rgb`: perceptual lightness, chroma and hue in degrees.
+    pub lch: [f32; 3],
+    pub oklch: [f32; 3],
+    /// Whether this cluster's centroid was pinned by the request.
+    pub pinned: bool,
+    /// How far the centroid moved onto its sample, with `snapToSamples`.
+    pub snap_distance: Option<f32>,
+    /// Where the cluster sits in the image, with `spatialWeight`.
+    pub region: Option<ClusterRegion>,
+    /// The [`MergeTree`](crate::merge::MergeTree) node this cluster is, with
+    /// `mergeThreshold`. Tree leaves are the unmerged non-empty and pinned
+    /// clusters.
+    pub merge_node: Option<usize>,
+}
+
+/// Leave out the empty clusters `build_clusters` would drop, so the merge
+/// tree's leaves match the palette. Labels are not kept.
+pub(super) fn drop_empty(
+    result: KMeansResult,
+    regions: &mut Vec<Option<ClusterRegion>>,
+    pinned: usize,
+) -> KMeansResult {
+    let keep: Vec<usize> = (0..result.counts.len())
+        .filter(|&idx| result.counts[idx] > 0 || idx < pinned)
+        .collect();
+    if !regions.is_empty() {
+        *regions = keep.iter().map(|&idx| regions[idx]).collect();
+    }
+    KMeansResult {
+        centroids: keep.iter().map(|&idx| result.centroids[idx]).collect(),
+        counts: keep.iter().map(|&idx| result.counts[idx]).collect(),
+        snap_distances: result
+            .snap_distances
+            .as_ref()
+            .map(|d| keep.iter().map(|&idx| d[idx]).collect()),
+        labels: None,
+        ..result
+    }
+}
+
+/// Convert k-means centroids back to RGB/HSV, dropping empty clusters and
+/// sorting by descending count. The first `pinned` centroids are kept even
+/// when empty. `regions` and `merge_nodes` are indexed like the centroids and
+/// empty unless the run was spatial or merged.
+pub fn build_clusters(
+    result: &KMeansResult,
+    space: ColorSpace,
+    total_samples: usize,
+    pinned: usize,
+    regions: &[Option<ClusterRegion>],
+    merge_nodes: &[usize],
+) -> Vec<ClusterOut> {
+    let mut clusters: Vec<ClusterOut> = result
+        .centroids
+        .iter()
+        .zip(result.counts.iter())
+        .enumerate()
+        .filter(|&(idx, (_, &count))| count > 0 || idx < pinned)
+        .map(|(idx, (centroid, &count))| {
+            let rgb = space.to_rgb8(*centroid);
+            ClusterOut {
+                count,
+                share: count as f64 / total_samples.max(1) as f64,
+                centroid_space: *centroid,
+                rgb: RgbValue::from(rgb),
+                hsv: color::rgb8_to_hsv(rgb),
+                lch: color::rgb8_to_lchab(rgb),
+                oklch: color::rgb8_to_oklch(rgb),
+                pinned: idx < pinned,
+                snap_distance: result.snap_distances.as_ref().map(|d| d[idx]),
+                region: regions.get(idx).copied().flatten(),
+                merge_node: merge_nodes.get(idx).copied(),
+            }
+        })
+        .collect();
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.count));
+    clusters
+}
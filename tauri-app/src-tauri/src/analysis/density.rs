@@ -0,0 +1,36 @@
+//! DBSCAN for the analysis pipeline, which picks the number of colors itself.
+
+use super::{distance_mode, AnalysisRequest, Result};
+use crate::kmeans::{run_dbscan, run_dbscan_weighted, DbscanConfig, KMeansResult};
+
+/// DBSCAN reported like the other algorithms, plus its noise count.
+pub(super) fn cluster_by_density(
+    dataset: &[[f32; 3]],
+    weights: Option<&[f32]>,
+    req: &AnalysisRequest,
+    total_samples: usize,
+) -> Result<(KMeansResult, usize)> {
+    let cfg = DbscanConfig {
+        eps: req.eps,
+        min_weight: req.min_share * total_samples as f32,
+        distance: distance_mode(req.space),
+        return_labels: false,
+    };
+    let found = match weights {
+        Some(weights) => run_dbscan_weighted(dataset, weights, &cfg)?,
+        None => run_dbscan(dataset, &cfg)?,
+    };
+    let result = KMeansResult {
+        centroids: found.centroids,
+        counts: found.counts,
+        iterations: 1,
+        inertia: found.inertia,
+        labels: None,
+        init: None,
+        converged: true,
+        trace: None,
+        empty_clusters: None,
+        snap_distances: None,
+    };
+    Ok((result, found.noise))
+}
@@ -0,0 +1,627 @@
+//! Shared palette analysis pipeline: samples → color space → k-means → clusters.
+//! The Tauri command, `compute_cli` and `rmpc-theme-gen` all go through [`analyze`]
+//! so that a fix to conversion or cluster ordering reaches every entry point.
+
+use std::time::Instant;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+use crate::algorithm::PaletteAlgorithm;
+use crate::color::{self, ColorSpace, DeltaE, SpaceConversion};
+use crate::image_pipeline::{prepare_samples, SampleParams, SampleResult, SamplingError};
+use crate::kmeans::{
+    run_kmeans, run_kmeans_weighted, AssignmentMode, DistanceMode, EmptyClusterStrategy,
+    KMeansConfig, KMeansError, KMeansInit,
+};
+use crate::merge::{merge_clusters, MergeConfig, MergeTree};
+
+mod clusters;
+mod density;
+mod spatial;
+
+use clusters::drop_empty;
+pub use clusters::{build_clusters, ClusterOut, RgbValue};
+use density::cluster_by_density;
+pub use spatial::ClusterRegion;
+use spatial::{cluster_spatially, merge_regions};
+
+#[derive(Debug, Error)]
+pub enum AnalysisError {
+    #[error("No file selected")]
+    NoImage,
+    #[error("Sampling failed: {0}")]
+    Sampling(#[from] SamplingError),
+    #[error("No pixels met sampling criteria (check stride/minLum)")]
+    NoSamples,
+    #[error("Axis weights must be finite and >= 0 (got {0:?})")]
+    InvalidWeights([f32; 3]),
+    #[error("Invalid pinned color '{0}' (expected #RRGGBB)")]
+    InvalidPin(String),
+    #[error("{pins} pinned colors but only {samples} samples to cluster")]
+    TooManyPins { pins: usize, samples: usize },
+    #[error("Merge threshold must be finite and >= 0 (got {0})")]
+    InvalidMergeThreshold(f32),
+    #[error("Spatial clustering needs sample positions (reservoir sampling with positions on)")]
+    MissingPositions,
+    #[error("Pinned colors can only be used with (non-spatial) k-means")]
+    PinsUnsupported,
+    #[error("No clusters found (every sample was noise; try a lower minShare or larger eps)")]
+    NoClusters,
+    #[error("Clustering failed: {0}")]
+    KMeans(#[from] KMeansError),
+}
+
+impl AnalysisError {
+    /// Stable camelCase tag the front-end can branch on.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NoImage => "noImage",
+            Self::Sampling(_) => "sampling",
+            Self::NoSamples => "noSamples",
+            Self::InvalidWeights(_) => "invalidWeights",
+            Self::InvalidPin(_) => "invalidPin",
+            Self::TooManyPins { .. } => "tooManyPins",
+            Self::InvalidMergeThreshold(_) => "invalidMergeThreshold",
+            Self::MissingPositions => "missingPositions",
+            Self::PinsUnsupported => "pinsUnsupported",
+            Self::NoClusters => "noClusters",
+            Self::KMeans(_) => "kmeans",
+        }
+    }
+}
+
+/// Serializes as `{ kind, message }` so Tauri commands can return it directly.
+impl Serialize for AnalysisError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AnalysisError", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AnalysisError>;
+
+#[derive(Debug, Clone)]
+pub struct AnalysisRequest {
+    pub space: ColorSpace,
+    pub k: usize,
+    pub max_iters: usize,
+    pub tol: f32,
+    pub seed: u64,
+    /// Per-axis distance weights in the clustering space (see `KMeansConfig::axis_weights`).
+    pub axis_weights: Option<[f32; 3]>,
+    /// Palette algorithm. The single-pass quantizers ignore `max_iters`, `tol`,
+    /// `seed` and `axis_weights`; DBSCAN also ignores `k`.
+    pub algorithm: PaletteAlgorithm,
+    /// k-means seeding (ignored by the single-pass quantizers).
+    pub init: KMeansInit,
+    /// Hex colors (`#RRGGBB`) kept as fixed k-means centroids; they count
+    /// towards `k`, which is raised to fit them. Other algorithms and spatial
+    /// k-means reject them with [`AnalysisError::PinsUnsupported`].
+    pub pinned: Vec<String>,
+    /// DBSCAN neighbourhood radius in clustering-space units (ΔE76 in CIELAB).
+    pub eps: f32,
+    /// Share of all samples a DBSCAN neighbourhood needs to seed a cluster.
+    pub min_share: f32,
+    /// Merge clusters closer than this ΔE after clustering (see [`merge_clusters`]).
+    pub merge_threshold: Option<f32>,
+    pub delta_e: DeltaE,
+    /// Move k-means centroids onto their nearest member sample so every
+    /// palette color occurs in the image. Ignored by the other algorithms.
+    pub snap_to_samples: bool,
+    /// Cluster color plus sample position, so palette entries are regions of
+    /// the image: crossing the whole image costs this many color units (ΔE76
+    /// in CIELAB). k-means only; the samples need positions, pins are
+    /// rejected, and `init` and `snap_to_samples` do not apply.
+    pub spatial_weight: Option<f32>,
+}
+
+impl Default for AnalysisRequest {
+    fn default() -> Self {
+        Self {
+            space: ColorSpace::Cielab,
+            k: 16,
+            max_iters: 40,
+            tol: 1e-3,
+            seed: 1,
+            axis_weights: None,
+            algorithm: PaletteAlgorithm::Kmeans,
+            init: KMeansInit::Auto,
+            pinned: Vec::new(),
+            eps: 4.0,
+            min_share: 0.002,
+            merge_threshold: None,
+            delta_e: DeltaE::Ciede2000,
+            snap_to_samples: false,
+            spatial_weight: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalysisResult {
+    /// Non-empty and pinned clusters sorted by descending count.
+    pub clusters: Vec<ClusterOut>,
+    pub iterations: usize,
+    pub inertia: f32,
+    /// Time spent in k-means only (sampling excluded).
+    pub duration_ms: f64,
+    pub total_samples: usize,
+    /// How k-means was seeded; `None` when a single-pass quantizer ran.
+    pub init: Option<KMeansInit>,
+    /// Samples DBSCAN left out of every cluster; 0 for the other algorithms.
+    pub noise_samples: usize,
+    /// Full merge order over the unmerged non-empty and pinned clusters, when
+    /// `merge_threshold` was set.
+    pub merge_tree: Option<MergeTree>,
+}
+
+/// Sample the image at `params.path` and run [`analyze_samples`] on the result.
+pub fn analyze_image(params: &SampleParams, req: &AnalysisRequest) -> Result<AnalysisResult> {
+    if params.path.as_os_str().is_empty() {
+        return Err(AnalysisError::NoImage);
+    }
+    let samples = prepare_samples(params)?;
+    analyze_samples(&samples, req)
+}
+
+/// Cluster sampler output, weighting histogram entries by their pixel counts
+/// and passing sample positions on to spatial clustering.
+pub fn analyze_samples(samples: &SampleResult, req: &AnalysisRequest) -> Result<AnalysisResult> {
+    match (&samples.counts, &samples.positions) {
+        (Some(counts), _) => analyze_weighted(&samples.samples, counts, req),
+        (None, Some(positions)) => analyze_spatial(&samples.samples, positions, req),
+        (None, None) => analyze(&samples.samples, req),
+    }
+}
+
+pub fn analyze(samples: &[[u8; 3]], req: &AnalysisRequest) -> Result<AnalysisResult> {
+    run_analysis(samples, None, None, req)
+}
+
+/// Cluster samples where `positions[i]` is where `samples[i]` was taken, as
+/// fractions of image width and height. Positions only matter with
+/// `req.spatial_weight`.
+pub fn analyze_spatial(
+    samples: &[[u8; 3]],
+    positions: &[[f32; 2]],
+    req: &AnalysisRequest,
+) -> Result<AnalysisResult> {
+    run_analysis(samples, None, Some(positions), req)
+}
+
+/// Cluster unique colors where `counts[i]` pixels had color `colors[i]`.
+pub fn analyze_weighted(
+    colors: &[[u8; 3]],
+    counts: &[u32],
+    req: &AnalysisRequest,
+) -> Result<AnalysisResult> {
+    run_analysis(colors, Some(counts), None, req)
+}
+
+fn run_analysis(
+    samples: &[[u8; 3]],
+    counts: Option<&[u32]>,
+    positions: Option<&[[f32; 2]]>,
+    req: &AnalysisRequest,
+) -> Result<AnalysisResult> {
+    if samples.is_empty() {
+        return Err(AnalysisError::NoSamples);
+    }
+    if let Some(weights) = req.axis_weights {
+        if !weights.iter().all(|w| w.is_finite() && *w >= 0.0) {
+            return Err(AnalysisError::InvalidWeights(weights));
+        }
+    }
+    if let Some(threshold) = req.merge_threshold {
+        if !(threshold.is_finite() && threshold >= 0.0) {
+            return Err(AnalysisError::InvalidMergeThreshold(threshold));
+        }
+    }
+
+    let plain_kmeans =
+        matches!(req.algorithm, PaletteAlgorithm::Kmeans) && req.spatial_weight.is_none();
+    if !req.pinned.is_empty() && !plain_kmeans {
+        return Err(AnalysisError::PinsUnsupported);
+    }
+    let pinned = req
+        .pinned
+        .iter()
+        .map(|hex| {
+            color::hex_to_rgb(hex)
+                .map(|rgb| req.space.to_space(rgb))
+                .ok_or_else(|| AnalysisError::InvalidPin(hex.clone()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if pinned.len() > samples.len() {
+        return Err(AnalysisError::TooManyPins {
+            pins: pinned.len(),
+            samples: samples.len(),
+        });
+    }
+
+    let dataset: Vec<[f32; 3]> = samples.iter().map(|&rgb| req.space.to_space(rgb)).collect();
+
+    let cfg = KMeansConfig {
+        k: req.k.clamp(1, dataset.len()).max(pinned.len()),
+        max_iters: req.max_iters,
+        tol: req.tol,
+        seed: req.seed,
+        warm_start: None,
+        pinned,
+        mini_batch: None,
+        distance: distance_mode(req.space),
+        axis_weights: req.axis_weights,
+        return_labels: false,
+        assignment: AssignmentMode::Lloyd,
+        n_init: 1,
+        init: req.init,
+        trace: false,
+        on_empty: EmptyClusterStrategy::Random,
+        snap_to_samples: req.snap_to_samples,
+    };
+    let weights: Option<Vec<f32>> = counts.map(|counts| counts.iter().map(|&c| c as f32).collect());
+    let total_samples = counts.map_or(samples.len(), |counts| {
+        counts.iter().map(|&c| c as usize).sum()
+    });
+    let start = Instant::now();
+    let spatial = match (req.algorithm, req.spatial_weight) {
+        (PaletteAlgorithm::Kmeans, Some(spatial_weight)) => {
+            let positions = positions
+                .filter(|p| counts.is_none() && p.len() == samples.len())
+                .ok_or(AnalysisError::MissingPositions)?;
+            Some((positions, spatial_weight))
+        }
+        _ => None,
+    };
+    let mut regions = Vec::new();
+    let (result, noise_samples) = match (req.algorithm, spatial) {
+        (_, Some((positions, spatial_weight))) => {
+            let (result, found) = cluster_spatially(&dataset, positions, &cfg, spatial_weight)?;
+            regions = found;
+            (result, 0)
+        }
+        (PaletteAlgorithm::Kmeans, None) => {
+            let result = match &weights {
+                Some(weights) => run_kmeans_weighted(&dataset, weights, &cfg)?,
+                None => run_kmeans(&dataset, &cfg)?,
+            };
+            (result, 0)
+        }
+        (PaletteAlgorithm::Quantizer(kind), None) => {
+            let result = kind
+                .quantizer()
+                .quantize(&dataset, weights.as_deref(), cfg.k)?;
+            (result, 0)
+        }
+        (PaletteAlgorithm::Dbscan, None) => {
+            cluster_by_density(&dataset, weights.as_deref(), req, total_samples)?
+        }
+    };
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let (result, merge_tree, merge_nodes) = match req.merge_threshold {
+        Some(threshold) => {
+            let result = drop_empty(result, &mut regions, cfg.pinned.len());
+            let merge_cfg = MergeConfig {
+                threshold,
+                delta_e: req.delta_e,
+                fixed: cfg.pinned.len(),
+            };
+            let (merged, tree) = merge_clusters(&result, req.space, &merge_cfg);
+            let merges = tree.merges_below(threshold);
+            if !regions.is_empty() {
+                regions = merge_regions(&regions, &result.counts, &tree.cut(merges));
+            }
+            let nodes = tree.roots(merges);
+            (merged, Some(tree), nodes)
+        }
+        None => (result, None, Vec::new()),
+    };
+
+    let clusters = build_clusters(
+        &result,
+        req.space,
+        total_samples,
+        cfg.pinned.len(),
+        &regions,
+        &merge_nodes,
+    );
+    if clusters.is_empty() {
+        return Err(AnalysisError::NoClusters);
+    }
+
+    Ok(AnalysisResult {
+        clusters,
+        iterations: result.iterations,
+        inertia: result.inertia,
+        duration_ms,
+        total_samples,
+        init: result.init,
+        noise_samples,
+        merge_tree,
+    })
+}
+
+/// Hue-bearing spaces cluster their hue circularly so 359° and 1° stay neighbours.
+pub fn distance_mode(space: ColorSpace) -> DistanceMode {
+    match space.hue_axis() {
+        Some(axis) => DistanceMode::Circular {
+            axis,
+            period: 360.0,
+        },
+        None => DistanceMode::Euclidean,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantize::QuantizerKind;
+
+    fn two_tone_samples() -> Vec<[u8; 3]> {
+        let mut samples = Vec::new();
+        for _ in 0..300 {
+            samples.push([220, 30, 40]);
+        }
+        for _ in 0..100 {
+            samples.push([20, 40, 200]);
+        }
+        samples
+    }
+
+    #[test]
+    fn every_space_recovers_two_tones() {
+        let samples = two_tone_samples();
+        for space in ColorSpace::ALL {
+            let req = AnalysisRequest {
+                space,
+                k: 2,
+                ..AnalysisRequest::default()
+            };
+            let result = analyze(&samples, &req).expect("analyze");
+            assert_eq!(result.clusters.len(), 2, "{space:?}");
+            assert_eq!(result.clusters[0].count, 300, "{space:?}");
+            let rgb = result.clusters[0].rgb.to_array();
+            for (got, want) in rgb.iter().zip([220u8, 30, 40]) {
+                assert!(got.abs_diff(want) <= 2, "{space:?}: {rgb:?}");
+            }
+            assert!((result.clusters[1].share - 0.25).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn k_is_clamped_to_sample_count() {
+        let samples = vec![[10, 10, 10], [240, 240, 240]];
+        let req = AnalysisRequest {
+            k: 16,
+            ..AnalysisRequest::default()
+        };
+        let result = analyze(&samples, &req).expect("analyze");
+        assert_eq!(result.clusters.len(), 2);
+        assert!(matches!(analyze(&[], &req), Err(AnalysisError::NoSamples)));
+    }
+
+    #[test]
+    fn weighted_colors_report_pixel_shares() {
+        let colors = [[220, 30, 40], [20, 40, 200]];
+        let req = AnalysisRequest {
+            k: 2,
+            ..AnalysisRequest::default()
+        };
+        let result = analyze_weighted(&colors, &[300, 100], &req).expect("analyze");
+        assert_eq!(result.total_samples, 400);
+        assert_eq!(result.clusters[0].count, 300);
+        assert_eq!(result.clusters[0].rgb.to_array(), [220, 30, 40]);
+        assert!((result.clusters[1].share - 0.25).abs() < 1e-9);
+        assert!(matches!(
+            analyze_weighted(&colors, &[300], &req),
+            Err(AnalysisError::KMeans(KMeansError::WeightsLengthMismatch {
+                points: 2,
+                weights: 1
+            }))
+        ));
+    }
+
+    #[test]
+    fn request_can_pick_a_single_pass_quantizer() {
+        let colors = [[220, 30, 40], [20, 40, 200]];
+        for kind in QuantizerKind::ALL {
+            let algorithm = PaletteAlgorithm::Quantizer(kind);
+            let req = AnalysisRequest {
+                k: 2,
+                algorithm,
+                ..AnalysisRequest::default()
+            };
+            let result = analyze_weighted(&colors, &[300, 100], &req).expect("analyze");
+            assert_eq!(result.iterations, 0, "{algorithm}");
+            assert_eq!(result.clusters[0].count, 300, "{algorithm}");
+            assert_eq!(
+                result.clusters[0].rgb.to_array(),
+                [220, 30, 40],
+                "{algorithm}"
+            );
+        }
+    }
+
+    #[test]
+    fn dbscan_finds_the_tones_without_k() {
+        let mut samples = two_tone_samples();
+        samples.push([0, 255, 0]);
+        let req = AnalysisRequest {
+            k: 9,
+            algorithm: PaletteAlgorithm::Dbscan,
+            // A cluster needs at least four samples
+            min_share: 0.01,
+            ..AnalysisRequest::default()
+        };
+        let result = analyze(&samples, &req).expect("analyze");
+        assert_eq!(result.clusters.len(), 2);
+        assert_eq!(result.clusters[0].count, 300);
+        assert_eq!(result.clusters[0].rgb.to_array(), [220, 30, 40]);
+        assert_eq!(result.noise_samples, 1);
+        assert_eq!(result.total_samples, 401);
+    }
+
+    #[test]
+    fn all_noise_is_an_error_not_an_empty_palette() {
+        // Fifty scattered colors, none with enough neighbours to seed a cluster
+        let samples: Vec<[u8; 3]> = (0..50u8)
+            .map(|i| [i * 5, 255 - i * 5, (u32::from(i) * 97 % 251) as u8])
+            .collect();
+        let req = AnalysisRequest {
+            algorithm: PaletteAlgorithm::Dbscan,
+            min_share: 0.2,
+            ..AnalysisRequest::default()
+        };
+        let err = analyze(&samples, &req).unwrap_err();
+        assert_eq!(err.kind(), "noClusters");
+    }
+
+    #[test]
+    fn near_duplicate_clusters_merge_into_one() {
+        let mut samples = two_tone_samples();
+        samples.extend(std::iter::repeat_n([222, 32, 40], 100));
+        let req = AnalysisRequest {
+            k: 3,
+            seed: 2,
+            merge_threshold: Some(3.0),
+            ..AnalysisRequest::default()
+        };
+        let result = analyze(&samples, &req).expect("analyze");
+        assert_eq!(result.clusters.len(), 2);
+        assert_eq!(result.clusters[0].count, 400);
+        let tree = result.merge_tree.expect("merge tree");
+        assert_eq!(tree.leaves, 3);
+        assert_eq!(tree.nodes.len(), 5);
+        for cluster in &result.clusters {
+            let node = &tree.nodes[cluster.merge_node.expect("merge node")];
+            let expected = (cluster.count, cluster.rgb.to_array());
+            assert_eq!((node.count, node.rgb), expected);
+        }
+
+        // Surplus clusters stay empty and never become leaves.
+        let wide = AnalysisRequest { k: 6, ..req };
+        let tree = analyze(&samples, &wide).unwrap().merge_tree.unwrap();
+        assert_eq!(tree.leaves, 3);
+
+        let bad = AnalysisRequest {
+            merge_threshold: Some(f32::NAN),
+            ..AnalysisRequest::default()
+        };
+        let err = analyze(&samples, &bad).unwrap_err();
+        assert_eq!(err.kind(), "invalidMergeThreshold");
+    }
+
+    #[test]
+    fn snapped_palette_uses_sample_colors() {
+        let mut samples = two_tone_samples();
+        samples.extend(std::iter::repeat_n([230, 40, 50], 100));
+        let req = AnalysisRequest {
+            k: 2,
+            snap_to_samples: true,
+            ..AnalysisRequest::default()
+        };
+        let result = analyze(&samples, &req).expect("analyze");
+        for cluster in &result.clusters {
+            assert!(samples.contains(&cluster.rgb.to_array()), "{cluster:?}");
+            assert!(cluster.snap_distance.is_some());
+        }
+        let red = &result.clusters[0];
+        assert_eq!(red.count, 400);
+        assert!(red.snap_distance.unwrap() > 0.0);
+
+        let plain = analyze(
+            &samples,
+            &AnalysisRequest {
+                snap_to_samples: false,
+                ..req
+            },
+        )
+        .expect("analyze");
+        assert!(plain.clusters.iter().all(|c| c.snap_distance.is_none()));
+    }
+
+    #[test]
+    fn errors_serialize_with_kind_and_message() {
+        let err = analyze_image(&SampleParams::new(""), &AnalysisRequest::default()).unwrap_err();
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({ "kind": "noImage", "message": "No file selected" })
+        );
+        let err = AnalysisError::from(KMeansError::EmptyData);
+        assert_eq!(serde_json::to_value(&err).unwrap()["kind"], "kmeans");
+    }
+
+    #[test]
+    fn negative_axis_weights_are_rejected() {
+        let samples = two_tone_samples();
+        let bad_weights = AnalysisRequest {
+            axis_weights: Some([1.0, -1.0, 1.0]),
+            ..AnalysisRequest::default()
+        };
+        assert!(matches!(
+            analyze(&samples, &bad_weights),
+            Err(AnalysisError::InvalidWeights(_))
+        ));
+    }
+
+    #[test]
+    fn pinned_colors_are_kept_even_when_unused() {
+        let samples = two_tone_samples();
+        let req = AnalysisRequest {
+            k: 3,
+            pinned: vec!["#D7263D".into()],
+            ..AnalysisRequest::default()
+        };
+        let result = analyze(&samples, &req).expect("analyze");
+        assert_eq!(result.clusters.len(), 3);
+        let pinned: Vec<&ClusterOut> = result.clusters.iter().filter(|c| c.pinned).collect();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].rgb.to_array(), [215, 38, 61]);
+        // The nearby red tone takes its own cluster, leaving the pin empty
+        assert_eq!(pinned[0].count, 0);
+        assert_eq!(result.clusters[0].count, 300);
+
+        // k grows to fit the pins
+        let crowded = AnalysisRequest {
+            k: 1,
+            pinned: vec!["#ffffff".into(), "#000000".into()],
+            ..AnalysisRequest::default()
+        };
+        assert_eq!(
+            analyze(&samples, &crowded).expect("analyze").clusters.len(),
+            2
+        );
+
+        let bad = AnalysisRequest {
+            pinned: vec!["red".into()],
+            ..AnalysisRequest::default()
+        };
+        let err = analyze(&samples, &bad).unwrap_err();
+        assert_eq!(err.kind(), "invalidPin");
+
+        let overpinned = AnalysisRequest {
+            pinned: vec!["#ffffff".into(), "#000000".into(), "#ff0000".into()],
+            ..AnalysisRequest::default()
+        };
+        let err = analyze(&samples[..2], &overpinned).unwrap_err();
+        assert_eq!(err.kind(), "tooManyPins");
+
+        for algorithm in [
+            PaletteAlgorithm::Dbscan,
+            PaletteAlgorithm::Quantizer(QuantizerKind::MedianCut),
+        ] {
+            let other = AnalysisRequest {
+                algorithm,
+                ..req.clone()
+            };
+            let err = analyze(&samples, &other).unwrap_err();
+            assert_eq!(err.kind(), "pinsUnsupported", "{algorithm}");
+        }
+    }
+}
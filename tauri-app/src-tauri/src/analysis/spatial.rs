@@ -0,0 +1,130 @@
+//! Spatial clustering for the analysis pipeline: k-means over color plus
+//! sample position, reported with the image region each cluster covers.
+
+use serde::Serialize;
+
+use super::Result;
+use crate::kmeans::{run_spatial_kmeans, KMeansConfig, KMeansResult, SpatialConfig};
+
+/// Image area covered by a spatial cluster, in fractions of width and height.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterRegion {
+    /// Mean member position `[x, y]`.
+    pub centroid: [f32; 2],
+    /// `[min_x, min_y, max_x, max_y]` over the members.
+    pub bounds: [f32; 4],
+}
+
+/// Spatial k-means reported like the other algorithms, plus one region per
+/// cluster (`None` for an empty one). The caller has rejected pins.
+pub(super) fn cluster_spatially(
+    dataset: &[[f32; 3]],
+    positions: &[[f32; 2]],
+    cfg: &KMeansConfig,
+    spatial_weight: f32,
+) -> Result<(KMeansResult, Vec<Option<ClusterRegion>>)> {
+    let spatial_cfg = SpatialConfig {
+        kmeans: cfg.clone(),
+        spatial_weight,
+    };
+    let found = run_spatial_kmeans(dataset, positions, &spatial_cfg)?;
+    let regions = found
+        .positions
+        .iter()
+        .zip(&found.bounds)
+        .map(|(&centroid, bounds)| bounds.map(|bounds| ClusterRegion { centroid, bounds }))
+        .collect();
+    Ok((found.kmeans, regions))
+}
+
+/// Combine the regions of clusters merged into the same group: count-weighted
+/// centroid and the union of the bounds.
+pub(super) fn merge_regions(
+    regions: &[Option<ClusterRegion>],
+    counts: &[usize],
+    groups: &[usize],
+) -> Vec<Option<ClusterRegion>> {
+    let len = groups.iter().max().map_or(0, |&g| g + 1);
+    let mut merged: Vec<Option<(ClusterRegion, usize)>> = vec![None; len];
+    for ((region, &count), &group) in regions.iter().zip(counts).zip(groups) {
+        let Some(region) = region else {
+            continue;
+        };
+        merged[group] = Some(match merged[group] {
+            None => (*region, count),
+            Some((acc, total)) => {
+                let sum = (total + count).max(1) as f32;
+                let (wa, wb) = (total as f32 / sum, count as f32 / sum);
+                let bounds = [
+                    acc.bounds[0].min(region.bounds[0]),
+                    acc.bounds[1].min(region.bounds[1]),
+                    acc.bounds[2].max(region.bounds[2]),
+                    acc.bounds[3].max(region.bounds[3]),
+                ];
+                let centroid = [
+                    wa * acc.centroid[0] + wb * region.centroid[0],
+                    wa * acc.centroid[1] + wb * region.centroid[1],
+                ];
+                (ClusterRegion { centroid, bounds }, total + count)
+            }
+        });
+    }
+    merged
+        .into_iter()
+        .map(|entry| entry.map(|(region, _)| region))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn spatial_clusters_report_their_regions() {
+        // A 10×10 image of one red; k = 4 splits it into quadrants
+        let samples = vec![[220, 30, 40]; 100];
+        let positions: Vec<[f32; 2]> = (0..100)
+            .map(|i| {
+                [
+                    ((i % 10) as f32 + 0.5) / 10.0,
+                    ((i / 10) as f32 + 0.5) / 10.0,
+                ]
+            })
+            .collect();
+        let req = AnalysisRequest {
+            k: 4,
+            spatial_weight: Some(40.0),
+            ..AnalysisRequest::default()
+        };
+        let result = analyze_spatial(&samples, &positions, &req).expect("analyze");
+        assert_eq!(result.clusters.len(), 4);
+        for cluster in &result.clusters {
+            assert_eq!(cluster.count, 25);
+            let region = cluster.region.expect("region");
+            let [x0, y0, x1, y1] = region.bounds;
+            assert!(x1 - x0 < 0.5 && y1 - y0 < 0.5, "{region:?}");
+        }
+
+        // Everything is one color, so merging folds the quadrants back together
+        let merged = AnalysisRequest {
+            merge_threshold: Some(3.0),
+            ..req.clone()
+        };
+        let result = analyze_spatial(&samples, &positions, &merged).expect("analyze");
+        assert_eq!(result.clusters.len(), 1);
+        let region = result.clusters[0].region.expect("region");
+        assert_eq!(region.bounds, [0.05, 0.05, 0.95, 0.95]);
+        assert!((region.centroid[0] - 0.5).abs() < 1e-5);
+
+        let err = analyze(&samples, &req).unwrap_err();
+        assert_eq!(err.kind(), "missingPositions");
+
+        let pinned = AnalysisRequest {
+            pinned: vec!["#DC1E28".into()],
+            ..req
+        };
+        let err = analyze_spatial(&samples, &positions, &pinned).unwrap_err();
+        assert_eq!(err.kind(), "pinsUnsupported");
+    }
+}
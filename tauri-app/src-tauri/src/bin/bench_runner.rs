@@ -5,10 +5,17 @@ use std::time::Instant;
 use anyhow::{bail, Context, Result};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use tauri_app::color;
+use tauri_app::color::{self, ColorSpace, SpaceConversion};
 #[cfg(feature = "bench-crate")]
 use tauri_app::kmeans::KMeansResult;
-use tauri_app::kmeans::{run_kmeans_soa, KMeansConfig, PointsSoa};
+use tauri_app::kmeans::{
+    run_kmeans_soa, AssignmentMode, DistanceMode, EmptyClusterStrategy, KMeansConfig, KMeansInit,
+    PointsSoa,
+};
+
+/// Restarts per job unless the manifest sets `nInit`: the warm-started run plus
+/// one fresh k-means++ seed.
+const DEFAULT_N_INIT: usize = 2;
 
 fn main() {
     if let Err(err) = run() {
@@ -226,7 +233,7 @@ fn process_job(
     explain_dir: Option<&Path>,
     parity: bool,
 ) -> Result<JobReport> {
-    let space = ColorSpace::try_from(job.options.space.as_str())?;
+    let space: ColorSpace = job.options.space.parse()?;
     let raw_samples = load_samples(sample_path, job.sample_count)
         .with_context(|| format!("load samples from {}", sample_path.display()))?;
 
@@ -249,7 +256,19 @@ fn process_job(
         tol: job.options.tol as f32,
         seed: job.options.seed as u64,
         warm_start: None,
+        pinned: Vec::new(),
         mini_batch: None,
+        // Euclidean on purpose: the JS reference clusters hue linearly.
+        distance: DistanceMode::Euclidean,
+        axis_weights: None,
+        return_labels: false,
+        assignment: AssignmentMode::Lloyd,
+        // The warm-started run competes with fresh k-means++ seeds; best inertia wins
+        n_init: job.options.n_init.unwrap_or(DEFAULT_N_INIT),
+        init: KMeansInit::KMeansPlusPlus,
+        trace: false,
+        on_empty: EmptyClusterStrategy::Random,
+        snap_to_samples: false,
     };
 
     let mut interactive_metrics = None;
@@ -269,7 +288,7 @@ fn process_job(
 
     let start = Instant::now();
     let (result, variant_label) = match variant {
-        ComputeVariant::Inhouse => (run_kmeans_soa(&dataset, &config), "inhouse"),
+        ComputeVariant::Inhouse => (run_kmeans_soa(&dataset, &config)?, "inhouse"),
         #[cfg(feature = "bench-crate")]
         ComputeVariant::Crate => (run_kmeans_crate(&dataset, &config)?, "crate"),
     };
@@ -278,50 +297,20 @@ fn process_job(
     let mut clusters = build_clusters(&result.centroids, &result.counts, total_samples, space);
     clusters.sort_by(|a, b| b.count.cmp(&a.count));
 
-    let interactive_clone = interactive_metrics.clone();
-    let mut rust_metrics = RustMetrics {
+    let rust_metrics = RustMetrics {
         duration_ms: duration.as_secs_f64() * 1000.0,
         iterations: result.iterations,
         inertia: result.inertia,
-        converged: result.iterations < config.max_iters,
+        converged: result.converged,
         total_samples,
         effective_k,
         variant: variant_label.to_string(),
         interactive: interactive_metrics,
     };
 
-    let mut comparisons =
+    let comparisons =
         build_cluster_comparisons(&job.js_clusters, &clusters, delta_metric, weighted);
 
-    let tail_limit = 6usize;
-    if comparisons.count_over_20 > tail_limit && warm_start_for_full.is_some() {
-        let mut fallback_cfg = config.clone();
-        fallback_cfg.warm_start = None;
-        fallback_cfg.seed = fallback_cfg.seed.wrapping_add(9973);
-        let fallback_start = Instant::now();
-        let fallback_result = run_kmeans_soa(&dataset, &fallback_cfg);
-        let fallback_duration = fallback_start.elapsed();
-        clusters = build_clusters(
-            &fallback_result.centroids,
-            &fallback_result.counts,
-            total_samples,
-            space,
-        );
-        clusters.sort_by(|a, b| b.count.cmp(&a.count));
-        rust_metrics = RustMetrics {
-            duration_ms: fallback_duration.as_secs_f64() * 1000.0,
-            iterations: fallback_result.iterations,
-            inertia: fallback_result.inertia,
-            converged: fallback_result.iterations < fallback_cfg.max_iters,
-            total_samples,
-            effective_k,
-            variant: variant_label.to_string(),
-            interactive: interactive_clone,
-        };
-        comparisons =
-            build_cluster_comparisons(&job.js_clusters, &clusters, delta_metric, weighted);
-    }
-
     if let Some(dir) = explain_dir {
         write_explain_report(
             dir,
@@ -364,6 +353,7 @@ fn run_interactive_preview(
     for attempt in 0..max_attempts {
         let mut cfg = base_cfg.clone();
         cfg.mini_batch = Some(batch_size);
+        cfg.n_init = 1;
         cfg.max_iters = options
             .interactive_max_iters
             .unwrap_or(cfg.max_iters as u32) as usize;
@@ -371,7 +361,10 @@ fn run_interactive_preview(
         cfg.warm_start = warm_start.clone();
 
         let start = Instant::now();
-        let result = run_kmeans_soa(dataset, &cfg);
+        // Keep the last good preview if a retry is rejected
+        let Ok(result) = run_kmeans_soa(dataset, &cfg) else {
+            break;
+        };
         let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
         let centroid_shift = warm_start
             .as_ref()
@@ -418,6 +411,12 @@ fn run_kmeans_crate(dataset: &PointsSoa, cfg: &KMeansConfig) -> Result<KMeansRes
             counts: Vec::new(),
             iterations: 0,
             inertia: 0.0,
+            labels: None,
+            init: None,
+            converged: true,
+            trace: None,
+            empty_clusters: None,
+            snap_distances: None,
         });
     }
 
@@ -457,6 +456,15 @@ fn run_kmeans_crate(dataset: &PointsSoa, cfg: &KMeansConfig) -> Result<KMeansRes
         counts,
         iterations: cfg.max_iters,
         inertia,
+        labels: cfg
+            .return_labels
+            .then(|| run.indices.iter().map(|&l| l as u32).collect()),
+        init: Some(KMeansInit::KMeansPlusPlus),
+        // kmeans_colors does not say whether it stopped on tolerance
+        converged: false,
+        trace: None,
+        empty_clusters: None,
+        snap_distances: None,
     })
 }
 
@@ -487,16 +495,7 @@ fn load_samples(path: &Path, declared_count: usize) -> Result<Vec<[u8; 3]>> {
 }
 
 fn build_dataset(samples: &[[u8; 3]], space: ColorSpace) -> PointsSoa {
-    let converted: Vec<[f32; 3]> = samples
-        .iter()
-        .map(|rgb| match space {
-            ColorSpace::Rgb => [rgb[0] as f32, rgb[1] as f32, rgb[2] as f32],
-            ColorSpace::Hsl => color::rgb8_to_hsl(*rgb),
-            ColorSpace::Yuv => rgb_to_yuv_js(*rgb),
-            ColorSpace::Cielab => color::rgb8_to_lab(*rgb),
-            ColorSpace::Cieluv => color::rgb8_to_luv(*rgb),
-        })
-        .collect();
+    let converted: Vec<[f32; 3]> = samples.iter().map(|&rgb| space.to_space(rgb)).collect();
     PointsSoa::from_points(&converted)
 }
 
@@ -514,7 +513,7 @@ fn build_clusters(
             if count == 0 {
                 return None;
             }
-            let rgb_u8 = space_to_rgb(*centroid, space);
+            let rgb_u8 = space.to_rgb8(*centroid);
             let rgb = RgbValue::from(rgb_u8);
             let hsv = color::rgb8_to_hsv(rgb_u8);
             Some(ClusterOutput {
@@ -537,40 +536,6 @@ fn clamp_channel(value: f32) -> u8 {
     value.round().clamp(0.0, 255.0) as u8
 }
 
-fn rgb_to_yuv_js(rgb: [u8; 3]) -> [f32; 3] {
-    let r = rgb[0] as f32;
-    let g = rgb[1] as f32;
-    let b = rgb[2] as f32;
-    let y = 0.299 * r + 0.587 * g + 0.114 * b;
-    let u = -0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0;
-    let v = 0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0;
-    [y, u, v]
-}
-
-fn yuv_to_rgb_js(yuv: [f32; 3]) -> [u8; 3] {
-    let y = yuv[0];
-    let u = yuv[1] - 128.0;
-    let v = yuv[2] - 128.0;
-    let r = y + 1.402 * v;
-    let g = y - 0.344_136 * u - 0.714_136 * v;
-    let b = y + 1.772 * u;
-    [clamp_channel(r), clamp_channel(g), clamp_channel(b)]
-}
-
-fn space_to_rgb(values: [f32; 3], space: ColorSpace) -> [u8; 3] {
-    match space {
-        ColorSpace::Rgb => [
-            clamp_channel(values[0]),
-            clamp_channel(values[1]),
-            clamp_channel(values[2]),
-        ],
-        ColorSpace::Hsl => color::hsl_to_rgb8(values),
-        ColorSpace::Yuv => yuv_to_rgb_js(values),
-        ColorSpace::Cielab => color::lab_to_rgb8(values),
-        ColorSpace::Cieluv => color::luv_to_rgb8(values),
-    }
-}
-
 fn rgb_delta(js_rgb: &RgbValue, rust_rgb: &RgbValue) -> f32 {
     let dr = (js_rgb.r - rust_rgb.r).abs();
     let dg = (js_rgb.g - rust_rgb.g).abs();
@@ -1254,29 +1219,6 @@ fn hungarian_min_cost(cost: &[f32], n: usize) -> Vec<usize> {
 
 // === Data structures ===
 
-#[derive(Debug, Clone, Copy)]
-enum ColorSpace {
-    Rgb,
-    Hsl,
-    Yuv,
-    Cielab,
-    Cieluv,
-}
-
-impl TryFrom<&str> for ColorSpace {
-    type Error = anyhow::Error;
-    fn try_from(value: &str) -> Result<Self> {
-        match value {
-            "RGB" => Ok(Self::Rgb),
-            "HSL" => Ok(Self::Hsl),
-            "YUV" => Ok(Self::Yuv),
-            "CIELAB" => Ok(Self::Cielab),
-            "CIELUV" => Ok(Self::Cieluv),
-            other => bail!("unsupported color space {other}"),
-        }
-    }
-}
-
 #[derive(Debug, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct SamplesManifest {
@@ -1322,6 +1264,8 @@ struct Options {
     interactive_min_batch: Option<usize>,
     #[serde(default)]
     interactive_shift_tol: Option<f32>,
+    #[serde(default)]
+    n_init: Option<usize>,
 }
 
 #[derive(Debug, Deserialize, Clone, Serialize)]
@@ -1,10 +1,13 @@
 use std::io::{self, Read};
-use std::time::Instant;
+use std::path::PathBuf;
 
-use rand::{rngs::SmallRng, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use tauri_app::color;
-use tauri_app::kmeans::{run_kmeans, KMeansConfig};
+use tauri_app::algorithm::PaletteAlgorithm;
+use tauri_app::analysis::{self, AnalysisRequest, ClusterOut};
+use tauri_app::color::{ColorSpace, DeltaE};
+use tauri_app::image_pipeline::{prepare_samples_from_buffer, SampleMode, SampleParams};
+use tauri_app::kmeans::KMeansInit;
+use tauri_app::merge::MergeTree;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -27,7 +30,29 @@ struct Request {
     #[serde(default = "default_seed")]
     seed: u64,
     #[serde(default = "default_space")]
-    space: String,
+    space: ColorSpace,
+    #[serde(default)]
+    axis_weights: Option<[f32; 3]>,
+    #[serde(default)]
+    histogram_bits: Option<u8>,
+    #[serde(default)]
+    algorithm: PaletteAlgorithm,
+    #[serde(default)]
+    init: KMeansInit,
+    #[serde(default)]
+    pinned: Vec<String>,
+    #[serde(default = "default_eps")]
+    eps: f32,
+    #[serde(default = "default_min_share")]
+    min_share: f32,
+    #[serde(default)]
+    merge_threshold: Option<f32>,
+    #[serde(default)]
+    delta_e: DeltaE,
+    #[serde(default)]
+    snap_to_samples: bool,
+    #[serde(default)]
+    spatial_weight: Option<f32>,
 }
 
 fn default_k() -> usize {
@@ -45,26 +70,14 @@ fn default_seed() -> u64 {
 fn default_max_samples() -> usize {
     300_000
 }
-fn default_space() -> String {
-    "CIELAB".into()
+fn default_space() -> ColorSpace {
+    ColorSpace::Cielab
 }
-
-#[derive(Debug, Serialize, Clone, Copy)]
-#[serde(rename_all = "camelCase")]
-struct RgbValue {
-    r: u8,
-    g: u8,
-    b: u8,
+fn default_eps() -> f32 {
+    4.0
 }
-
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ClusterOut {
-    count: usize,
-    share: f64,
-    centroid_space: [f32; 3],
-    rgb: RgbValue,
-    hsv: [f32; 3],
+fn default_min_share() -> f32 {
+    0.002
 }
 
 #[derive(Debug, Serialize)]
@@ -75,6 +88,10 @@ struct AnalyzeResponse {
     duration_ms: f64,
     total_samples: usize,
     variant: String,
+    // Seeding that produced the clusters; null for single-pass quantizers
+    init: Option<KMeansInit>,
+    noise_samples: usize,
+    merge_tree: Option<MergeTree>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -84,123 +101,49 @@ fn main() -> anyhow::Result<()> {
         anyhow::bail!("expected JSON request on stdin");
     }
     let req: Request = serde_json::from_str(&buf)?;
-    let width = req.width as usize;
-    let height = req.height as usize;
-    if width == 0 || height == 0 {
-        anyhow::bail!("width/height must be > 0");
-    }
-    let pixels = width * height;
-    let channels = match req.data.len() / pixels {
-        3 => 3,
-        4 => 4,
-        other => anyhow::bail!("unsupported channel count: {other}"),
-    };
-
-    let stride = req.stride.max(1) as usize;
-    let max_samples = if req.max_samples == 0 {
-        usize::MAX
-    } else {
-        req.max_samples
-    };
-    let min_lum = req.min_lum as f32;
-
-    let mut rng = SmallRng::seed_from_u64(req.seed);
-    let mut samples: Vec<[u8; 3]> = Vec::with_capacity(max_samples.min(pixels));
-    let mut seen = 0usize;
-    for y in (0..height).step_by(stride) {
-        for x in (0..width).step_by(stride) {
-            let idx = (y * width + x) * channels;
-            let r = req.data[idx];
-            let g = req.data[idx + 1];
-            let b = req.data[idx + 2];
-            let lum = 0.2126 * (r as f32) + 0.7152 * (g as f32) + 0.0722 * (b as f32);
-            if lum < min_lum {
-                continue;
-            }
-            seen += 1;
-            if samples.len() < max_samples {
-                samples.push([r, g, b]);
-            } else {
-                let ridx = rng.gen_range(0..seen);
-                if ridx < max_samples {
-                    samples[ridx] = [r, g, b];
-                }
-            }
-        }
-    }
 
-    if samples.is_empty() {
-        anyhow::bail!("no pixels met sampling criteria (check stride/minLum)");
-    }
-
-    let space_upper = req.space.to_ascii_uppercase();
-    let dataset: Vec<[f32; 3]> = match space_upper.as_str() {
-        "CIELAB" | "LAB" => samples.iter().map(|&rgb| color::rgb8_to_lab(rgb)).collect(),
-        "RGB" => samples
-            .iter()
-            .map(|&rgb| [rgb[0] as f32, rgb[1] as f32, rgb[2] as f32])
-            .collect(),
-        "HSL" => samples.iter().map(|&rgb| color::rgb8_to_hsl(rgb)).collect(),
-        "HSV" => samples.iter().map(|&rgb| color::rgb8_to_hsv(rgb)).collect(),
-        "YUV" => samples.iter().map(|&rgb| color::rgb8_to_yuv(rgb)).collect(),
-        "CIELUV" | "LUV" => samples.iter().map(|&rgb| color::rgb8_to_luv(rgb)).collect(),
-        _ => anyhow::bail!("unsupported color space"),
+    let sample_params = SampleParams {
+        path: PathBuf::new(),
+        stride: req.stride.max(1),
+        min_lum: req.min_lum,
+        max_samples: req.max_samples,
+        max_dimension: None,
+        seed: req.seed,
+        mode: req
+            .histogram_bits
+            .map_or(SampleMode::Reservoir, |bits| SampleMode::Histogram { bits }),
+        positions: req.spatial_weight.is_some(),
     };
+    let samples = prepare_samples_from_buffer(req.width, req.height, &req.data, &sample_params)?;
 
-    let k = req.k.min(dataset.len().max(1));
-    let cfg = KMeansConfig {
-        k,
+    let request = AnalysisRequest {
+        space: req.space,
+        k: req.k,
         max_iters: req.max_iter as usize,
         tol: req.tol,
         seed: req.seed,
-        warm_start: None,
-        mini_batch: None,
+        axis_weights: req.axis_weights,
+        algorithm: req.algorithm,
+        init: req.init,
+        pinned: req.pinned,
+        eps: req.eps,
+        min_share: req.min_share,
+        merge_threshold: req.merge_threshold,
+        delta_e: req.delta_e,
+        snap_to_samples: req.snap_to_samples,
+        spatial_weight: req.spatial_weight,
     };
-
-    let start = Instant::now();
-    let result = run_kmeans(&dataset, &cfg);
-    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
-
-    let mut clusters: Vec<ClusterOut> = Vec::with_capacity(result.centroids.len());
-    for (centroid, &count) in result.centroids.iter().zip(result.counts.iter()) {
-        if count == 0 {
-            continue;
-        }
-        let rgb_u8 = match space_upper.as_str() {
-            "RGB" => [
-                centroid[0].round().clamp(0.0, 255.0) as u8,
-                centroid[1].round().clamp(0.0, 255.0) as u8,
-                centroid[2].round().clamp(0.0, 255.0) as u8,
-            ],
-            "HSL" => color::hsl_to_rgb8(*centroid),
-            "HSV" => color::hsv_to_rgb8(*centroid),
-            "YUV" => color::yuv_to_rgb8(*centroid),
-            "CIELAB" | "LAB" => color::lab_to_rgb8(*centroid),
-            "CIELUV" | "LUV" => color::luv_to_rgb8(*centroid),
-            _ => unreachable!(),
-        };
-        let rgb = RgbValue {
-            r: rgb_u8[0],
-            g: rgb_u8[1],
-            b: rgb_u8[2],
-        };
-        let hsv = color::rgb8_to_hsv(rgb_u8);
-        clusters.push(ClusterOut {
-            count,
-            share: (count as f64) / (samples.len() as f64),
-            centroid_space: *centroid,
-            rgb,
-            hsv,
-        });
-    }
-    clusters.sort_by(|a, b| b.count.cmp(&a.count));
+    let result = analysis::analyze_samples(&samples, &request)?;
 
     let resp = AnalyzeResponse {
-        clusters,
+        clusters: result.clusters,
         iterations: result.iterations,
-        duration_ms,
-        total_samples: samples.len(),
+        duration_ms: result.duration_ms,
+        total_samples: result.total_samples,
         variant: "native".into(),
+        init: result.init,
+        noise_samples: result.noise_samples,
+        merge_tree: result.merge_tree,
     };
 
     println!("{}", serde_json::to_string_pretty(&resp)?);
@@ -1,7 +1,7 @@
 use std::time::Instant;
 
 use rand::{rngs::SmallRng, Rng, SeedableRng};
-use tauri_app::kmeans::{run_kmeans, KMeansConfig};
+use tauri_app::kmeans::{run_kmeans, AssignmentMode, KMeansConfig, KMeansResult};
 
 const SAMPLE_COUNT: usize = 120_000;
 const BASE_CLUSTERS: usize = 40;
@@ -11,32 +11,49 @@ fn main() {
     println!("Baseline k-means timing ({} samples)", SAMPLE_COUNT);
     let dataset = generate_dataset();
     let ks = [64usize, 128, 300];
+    let modes = [AssignmentMode::Lloyd, AssignmentMode::Hamerly];
 
     for &k in &ks {
-        let mut durations = Vec::new();
-        for run_idx in 0..RUNS {
-            let cfg = KMeansConfig {
-                k,
-                max_iters: 40,
-                tol: 1e-3,
-                seed: run_idx as u64 + 1,
-                warm_start: None,
-                mini_batch: None,
-            };
-            let start = Instant::now();
-            let result = run_kmeans(&dataset, &cfg);
-            let elapsed = start.elapsed();
-            durations.push(elapsed);
+        let mut reference: Vec<KMeansResult> = Vec::new();
+        for mode in modes {
+            let mut durations = Vec::new();
+            for run_idx in 0..RUNS {
+                let cfg = KMeansConfig {
+                    k,
+                    max_iters: 40,
+                    tol: 1e-3,
+                    seed: run_idx as u64 + 1,
+                    warm_start: None,
+                    mini_batch: None,
+                    assignment: mode,
+                    ..KMeansConfig::default()
+                };
+                let start = Instant::now();
+                let result = run_kmeans(&dataset, &cfg).expect("k-means on generated data");
+                let elapsed = start.elapsed();
+                durations.push(elapsed);
+                println!(
+                    "  k={k:>3} {mode:?} run #{run_idx} -> {:.2?} (iters: {}, inertia: {:.2})",
+                    elapsed, result.iterations, result.inertia
+                );
+                // Accelerated modes must reproduce the brute-force clustering
+                match reference.get(run_idx) {
+                    Some(expected) => {
+                        assert_eq!(result.counts, expected.counts, "{mode:?} counts diverged");
+                        assert_eq!(
+                            result.centroids, expected.centroids,
+                            "{mode:?} centroids diverged"
+                        );
+                    }
+                    None => reference.push(result),
+                }
+            }
+            let avg_ns: u128 = durations.iter().map(|d| d.as_nanos()).sum::<u128>() / RUNS as u128;
             println!(
-                "  k={k:>3} run #{run_idx} -> {:.2?} (iters: {}, inertia: {:.2})",
-                elapsed, result.iterations, result.inertia
+                "  k={k:>3} {mode:?} avg {:.2?}\n",
+                std::time::Duration::from_nanos(avg_ns as u64)
             );
         }
-        let avg_ns: u128 = durations.iter().map(|d| d.as_nanos()).sum::<u128>() / RUNS as u128;
-        println!(
-            "  k={k:>3} avg {:.2?}\n",
-            std::time::Duration::from_nanos(avg_ns as u64)
-        );
     }
 }
 
@@ -4,9 +4,11 @@ use std::time::Instant;
 use anyhow::{Context, Result};
 use clap::Parser;
 use serde::Serialize;
-use tauri_app::color;
-use tauri_app::image_pipeline::{prepare_samples, SampleParams};
-use tauri_app::kmeans::{run_kmeans, KMeansConfig};
+use tauri_app::algorithm::PaletteAlgorithm;
+use tauri_app::analysis::{self, AnalysisRequest, RgbValue};
+use tauri_app::color::{self, ColorSpace, DeltaE};
+use tauri_app::image_pipeline::{prepare_samples, SampleMode, SampleParams};
+use tauri_app::kmeans::KMeansInit;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 enum ColorRole {
@@ -44,9 +46,37 @@ struct Args {
     #[arg(short, long, default_value = "8")]
     k: usize,
 
-    /// Color space for clustering (CIELAB, RGB, HSL, HSV, YUV, CIELUV)
+    /// Color space for clustering (CIELAB, RGB, HSL, HSV, YUV, CIELUV, LCHAB, LCHUV, OKLAB, OKLCH)
     #[arg(short, long, default_value = "CIELAB")]
-    space: String,
+    space: ColorSpace,
+
+    /// Per-axis distance weights in the clustering space, e.g. `--weights 0.5,1,1`
+    #[arg(long, value_delimiter = ',', num_args = 3)]
+    weights: Option<Vec<f32>>,
+
+    /// Cluster every pixel via a color histogram with this many bits per channel (1-8)
+    #[arg(long)]
+    histogram_bits: Option<u8>,
+
+    /// Palette algorithm (kmeans, median-cut, octree, wu, dbscan)
+    #[arg(long, default_value = "kmeans")]
+    algorithm: PaletteAlgorithm,
+
+    /// DBSCAN neighbourhood radius in clustering-space units (ΔE76 in CIELAB)
+    #[arg(long, default_value = "4")]
+    eps: f32,
+
+    /// Merge clusters closer than this CIEDE2000 ΔE (e.g. 3)
+    #[arg(long)]
+    merge: Option<f32>,
+
+    /// Use real image colors: move each k-means centroid onto its nearest sample
+    #[arg(long)]
+    snap: bool,
+
+    /// Keep this hex color (#RRGGBB) in the palette; repeatable, k-means only
+    #[arg(long = "pin")]
+    pin: Vec<String>,
 
     /// Output file path (stdout if not specified)
     #[arg(short, long)]
@@ -57,20 +87,14 @@ struct Args {
     theme_output: Option<PathBuf>,
 }
 
-#[derive(Debug, Serialize, Clone, Copy)]
-#[serde(rename_all = "camelCase")]
-struct RgbValue {
-    r: u8,
-    g: u8,
-    b: u8,
-}
-
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct ColorCluster {
     rgb: RgbValue,
     hsv: [f32; 3],
     lab: [f32; 3],
+    lch: [f32; 3],
+    oklch: [f32; 3],
     count: usize,
     share: f64,
 }
@@ -83,7 +107,7 @@ struct ThemeGenOutput {
     total_samples: usize,
     iterations: usize,
     duration_ms: f64,
-    color_space: String,
+    color_space: ColorSpace,
 }
 
 /// Select background color: prefer most dominant with reasonable saturation/lightness
@@ -608,6 +632,10 @@ fn main() -> Result<()> {
         max_samples: 300_000,
         max_dimension: Some(3200),
         seed: 1,
+        mode: args
+            .histogram_bits
+            .map_or(SampleMode::Reservoir, |bits| SampleMode::Histogram { bits }),
+        positions: false,
     };
 
     // Sample pixels from image
@@ -618,100 +646,46 @@ fn main() -> Result<()> {
         anyhow::bail!("No pixels sampled from image");
     }
 
-    // Convert samples to chosen color space
-    let space_upper = args.space.to_ascii_uppercase();
-    let dataset: Vec<[f32; 3]> = match space_upper.as_str() {
-        "CIELAB" | "LAB" => sample_result
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_lab(rgb))
-            .collect(),
-        "RGB" => sample_result
-            .samples
-            .iter()
-            .map(|&rgb| [rgb[0] as f32, rgb[1] as f32, rgb[2] as f32])
-            .collect(),
-        "HSL" => sample_result
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_hsl(rgb))
-            .collect(),
-        "HSV" => sample_result
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_hsv(rgb))
-            .collect(),
-        "YUV" => sample_result
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_yuv(rgb))
-            .collect(),
-        "CIELUV" | "LUV" => sample_result
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_luv(rgb))
-            .collect(),
-        _ => anyhow::bail!("Unsupported color space: {}", args.space),
-    };
-
-    // Run K-means clustering
-    let k = args.k.min(dataset.len().max(1));
-    let kmeans_config = KMeansConfig {
-        k,
+    // Cluster in the chosen color space
+    let request = AnalysisRequest {
+        space: args.space,
+        k: args.k,
         max_iters: 40,
         tol: 1e-3,
         seed: 1,
-        warm_start: None,
-        mini_batch: None,
+        axis_weights: args.weights.as_deref().map(|w| [w[0], w[1], w[2]]),
+        algorithm: args.algorithm,
+        init: KMeansInit::Auto,
+        pinned: args.pin.clone(),
+        eps: args.eps,
+        min_share: 0.002,
+        merge_threshold: args.merge,
+        delta_e: DeltaE::Ciede2000,
+        snap_to_samples: args.snap,
+        spatial_weight: None,
     };
+    let analysis_result = analysis::analyze_samples(&sample_result, &request)?;
 
-    let kmeans_result = run_kmeans(&dataset, &kmeans_config);
-
-    // Convert centroids to all color spaces
-    let mut clusters: Vec<ColorCluster> = Vec::with_capacity(kmeans_result.centroids.len());
-    for (centroid, &count) in kmeans_result
-        .centroids
+    // Clusters arrive sorted by count (descending); add Lab for role mapping
+    let clusters: Vec<ColorCluster> = analysis_result
+        .clusters
         .iter()
-        .zip(kmeans_result.counts.iter())
-    {
-        if count == 0 {
-            continue;
-        }
-
-        // Convert centroid from clustering space to RGB
-        let rgb_u8 = match space_upper.as_str() {
-            "RGB" => [
-                centroid[0].round().clamp(0.0, 255.0) as u8,
-                centroid[1].round().clamp(0.0, 255.0) as u8,
-                centroid[2].round().clamp(0.0, 255.0) as u8,
-            ],
-            "HSL" => color::hsl_to_rgb8(*centroid),
-            "HSV" => color::hsv_to_rgb8(*centroid),
-            "YUV" => color::yuv_to_rgb8(*centroid),
-            "CIELAB" | "LAB" => color::lab_to_rgb8(*centroid),
-            "CIELUV" | "LUV" => color::luv_to_rgb8(*centroid),
-            _ => unreachable!(),
-        };
-
-        let hsv = color::rgb8_to_hsv(rgb_u8);
-        let lab = color::rgb8_to_lab(rgb_u8);
-
-        clusters.push(ColorCluster {
-            rgb: RgbValue {
-                r: rgb_u8[0],
-                g: rgb_u8[1],
-                b: rgb_u8[2],
-            },
-            hsv,
-            lab,
-            count,
-            share: (count as f64) / (sample_result.sampled_pixels as f64),
-        });
+        .map(|cluster| ColorCluster {
+            rgb: cluster.rgb,
+            hsv: cluster.hsv,
+            lab: color::rgb8_to_lab(cluster.rgb.to_array()),
+            lch: cluster.lch,
+            oklch: cluster.oklch,
+            count: cluster.count,
+            share: cluster.share,
+        })
+        .collect();
+
+    // Role mapping indexes into the palette, so it needs at least one color
+    if clusters.is_empty() {
+        anyhow::bail!("Clustering produced no colors to map to theme roles");
     }
 
-    // Sort clusters by count (descending) for consistency
-    clusters.sort_by(|a, b| b.count.cmp(&a.count));
-
     // Map colors to theme element roles
     let role_assignments = map_colors_to_roles(&clusters);
 
@@ -737,9 +711,9 @@ fn main() -> Result<()> {
         clusters,
         role_assignments,
         total_samples: sample_result.sampled_pixels,
-        iterations: kmeans_result.iterations,
+        iterations: analysis_result.iterations,
         duration_ms,
-        color_space: args.space.clone(),
+        color_space: args.space,
     };
 
     // Serialize to JSON
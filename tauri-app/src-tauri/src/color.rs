@@ -5,6 +5,14 @@
 //! - Color-tool by L. Jégou (CC BY 3.0): https://github.com/ljegou/Color-tool
 //! - CIE 15:2018 (Colorimetry, 4th Edition) for LAB/LUV
 //! - IEC 61966-2-1:1999 for sRGB gamma and XYZ transforms
+//! - B. Ottosson, "A perceptual color space for image processing" (2020) for OKLab
+//! - G. Sharma, W. Wu, E. Dalal, "The CIEDE2000 Color-Difference Formula" (2005)
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 const EPSILON: f32 = 1e-6;
 const XYZ_WHITE: [f32; 3] = [0.95047, 1.0, 1.08883]; // D65
@@ -188,10 +196,10 @@ pub fn yuv_to_rgb8(yuv: [f32; 3]) -> [u8; 3] {
     let y = yuv[0];
     let u = yuv[1];
     let v = yuv[2];
-    // BT.601 inverse (matching CC BY 3.0 Color-tool reference)
-    let r = y + 1.4075 * (v - 128.0);
-    let g = y - 0.3455 * (u - 128.0) - 0.7169 * (v - 128.0);
-    let b = y + 1.779 * (u - 128.0);
+    // Exact BT.601 full-range inverse of `rgb8_to_yuv` (matches the JS renderer)
+    let r = y + 1.402 * (v - 128.0);
+    let g = y - 0.344_136 * (u - 128.0) - 0.714_136 * (v - 128.0);
+    let b = y + 1.772 * (u - 128.0);
     [to_u8(r / 255.0), to_u8(g / 255.0), to_u8(b / 255.0)]
 }
 
@@ -291,6 +299,74 @@ pub fn hsv_to_rgb8(hsv: [f32; 3]) -> [u8; 3] {
     [to_u8(r1 + m), to_u8(g1 + m), to_u8(b1 + m)]
 }
 
+pub fn rgb8_to_oklab(rgb: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = srgb8_to_linear(rgb);
+    let l = (0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+pub fn oklab_to_rgb8(lab: [f32; 3]) -> [u8; 3] {
+    let [ok_l, ok_a, ok_b] = lab;
+    let l = (ok_l + 0.396_337_78 * ok_a + 0.215_803_76 * ok_b).powi(3);
+    let m = (ok_l - 0.105_561_346 * ok_a - 0.063_854_17 * ok_b).powi(3);
+    let s = (ok_l - 0.089_484_18 * ok_a - 1.291_485_5 * ok_b).powi(3);
+    let linear = [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ];
+    linear_to_srgb8(linear.map(clamp01))
+}
+
+/// Cylindrical form of any lightness + opponent-axes space (CIELAB, CIELUV, OKLab).
+/// Returns `[L, C, h]` with `h` in degrees `[0, 360)`; achromatic colors get `h = 0`.
+pub fn lab_to_lch(lab: [f32; 3]) -> [f32; 3] {
+    let c = lab[1].hypot(lab[2]);
+    let h = if c < EPSILON {
+        0.0
+    } else {
+        lab[2].atan2(lab[1]).to_degrees().rem_euclid(360.0)
+    };
+    [lab[0], c, h]
+}
+
+pub fn lch_to_lab(lch: [f32; 3]) -> [f32; 3] {
+    let (sin, cos) = lch[2].to_radians().sin_cos();
+    [lch[0], lch[1] * cos, lch[1] * sin]
+}
+
+pub fn rgb8_to_oklch(rgb: [u8; 3]) -> [f32; 3] {
+    lab_to_lch(rgb8_to_oklab(rgb))
+}
+
+pub fn oklch_to_rgb8(lch: [f32; 3]) -> [u8; 3] {
+    oklab_to_rgb8(lch_to_lab(lch))
+}
+
+/// CIE LCh(ab), a.k.a. HCL: cylindrical CIELAB.
+pub fn rgb8_to_lchab(rgb: [u8; 3]) -> [f32; 3] {
+    lab_to_lch(rgb8_to_lab(rgb))
+}
+
+pub fn lchab_to_rgb8(lch: [f32; 3]) -> [u8; 3] {
+    lab_to_rgb8(lch_to_lab(lch))
+}
+
+/// CIE LCh(uv): cylindrical CIELUV.
+pub fn rgb8_to_lchuv(rgb: [u8; 3]) -> [f32; 3] {
+    lab_to_lch(rgb8_to_luv(rgb))
+}
+
+pub fn lchuv_to_rgb8(lch: [f32; 3]) -> [u8; 3] {
+    luv_to_rgb8(lch_to_lab(lch))
+}
+
 pub fn hue_to_radians(h: f32) -> f32 {
     h.to_radians()
 }
@@ -325,11 +401,237 @@ pub fn delta_e_cie76(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
     (dl * dl + da * da + db * db).sqrt()
 }
 
+/// CIEDE2000 color difference (kL = kC = kH = 1), following Sharma et al.
+/// Tracks perceived difference far better than CIE76 for saturated blues and
+/// near-neutrals.
+pub fn delta_e_ciede2000(lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+    let [l1, a1, b1] = lab1.map(f64::from);
+    let [l2, a2, b2] = lab2.map(f64::from);
+    let pow25_7 = 25f64.powi(7);
+
+    let c_bar = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt());
+    let (a1p, a2p) = ((1.0 + g) * a1, (1.0 + g) * a2);
+    let (c1p, c2p) = ((a1p * a1p + b1 * b1).sqrt(), (a2p * a2p + b2 * b2).sqrt());
+    let hue = |b: f64, a: f64| {
+        if a == 0.0 && b == 0.0 {
+            0.0
+        } else {
+            b.atan2(a).to_degrees().rem_euclid(360.0)
+        }
+    };
+    let (h1p, h2p) = (hue(b1, a1p), hue(b2, a2p));
+    let chromatic = c1p * c2p != 0.0;
+
+    let dl = l2 - l1;
+    let dc = c2p - c1p;
+    let dh_angle = match h2p - h1p {
+        _ if !chromatic => 0.0,
+        d if d > 180.0 => d - 360.0,
+        d if d < -180.0 => d + 360.0,
+        d => d,
+    };
+    let dh = 2.0 * (c1p * c2p).sqrt() * (dh_angle / 2.0).to_radians().sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar_p = (c1p + c2p) / 2.0;
+    let h_bar = if !chromatic {
+        h1p + h2p
+    } else if (h1p - h2p).abs() <= 180.0 {
+        (h1p + h2p) / 2.0
+    } else if h1p + h2p < 360.0 {
+        (h1p + h2p + 360.0) / 2.0
+    } else {
+        (h1p + h2p - 360.0) / 2.0
+    };
+    let cos_deg = |deg: f64| deg.to_radians().cos();
+    let t = 1.0 - 0.17 * cos_deg(h_bar - 30.0)
+        + 0.24 * cos_deg(2.0 * h_bar)
+        + 0.32 * cos_deg(3.0 * h_bar + 6.0)
+        - 0.20 * cos_deg(4.0 * h_bar - 63.0);
+    let d_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let rc = 2.0 * (c_bar_p.powi(7) / (c_bar_p.powi(7) + pow25_7)).sqrt();
+    let l_off = (l_bar - 50.0).powi(2);
+    let sl = 1.0 + 0.015 * l_off / (20.0 + l_off).sqrt();
+    let sc = 1.0 + 0.045 * c_bar_p;
+    let sh = 1.0 + 0.015 * c_bar_p * t;
+    let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+
+    let (tl, tc, th) = (dl / sl, dc / sc, dh / sh);
+    (tl * tl + tc * tc + th * th + rt * tc * th).max(0.0).sqrt() as f32
+}
+
+/// Color-difference formula for perceptual thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeltaE {
+    Cie76,
+    #[default]
+    Ciede2000,
+}
+
+impl DeltaE {
+    pub fn between(self, lab1: [f32; 3], lab2: [f32; 3]) -> f32 {
+        match self {
+            Self::Cie76 => delta_e_cie76(lab1, lab2),
+            Self::Ciede2000 => delta_e_ciede2000(lab1, lab2),
+        }
+    }
+}
+
 /// Convert RGB to hex string format (#RRGGBB)
 pub fn rgb_to_hex(rgb: [u8; 3]) -> String {
     format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2])
 }
 
+/// Parse `#RRGGBB` (the `#` is optional, digits are case-insensitive).
+pub fn hex_to_rgb(hex: &str) -> Option<[u8; 3]> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Working space for clustering. Parsing is case-insensitive and accepts the
+/// `LAB`/`LUV` shorthands; `Display` and serde use the canonical names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ColorSpace {
+    Rgb,
+    Hsl,
+    Hsv,
+    Yuv,
+    Cielab,
+    Cieluv,
+    Lchab,
+    Lchuv,
+    Oklab,
+    Oklch,
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("unsupported color space '{0}' (RGB|HSL|HSV|YUV|CIELAB|CIELUV|LCHAB|LCHUV|OKLAB|OKLCH)")]
+pub struct ParseColorSpaceError(pub String);
+
+impl ColorSpace {
+    pub const ALL: [ColorSpace; 10] = [
+        ColorSpace::Rgb,
+        ColorSpace::Hsl,
+        ColorSpace::Hsv,
+        ColorSpace::Yuv,
+        ColorSpace::Cielab,
+        ColorSpace::Cieluv,
+        ColorSpace::Lchab,
+        ColorSpace::Lchuv,
+        ColorSpace::Oklab,
+        ColorSpace::Oklch,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Rgb => "RGB",
+            Self::Hsl => "HSL",
+            Self::Hsv => "HSV",
+            Self::Yuv => "YUV",
+            Self::Cielab => "CIELAB",
+            Self::Cieluv => "CIELUV",
+            Self::Lchab => "LCHAB",
+            Self::Lchuv => "LCHUV",
+            Self::Oklab => "OKLAB",
+            Self::Oklch => "OKLCH",
+        }
+    }
+
+    /// Index of the hue component in degrees, for spaces with a cylindrical hue.
+    pub fn hue_axis(self) -> Option<usize> {
+        match self {
+            Self::Hsl | Self::Hsv => Some(0),
+            Self::Lchab | Self::Lchuv | Self::Oklch => Some(2),
+            Self::Rgb | Self::Yuv | Self::Cielab | Self::Cieluv | Self::Oklab => None,
+        }
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ColorSpace {
+    type Err = ParseColorSpaceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "RGB" => Ok(Self::Rgb),
+            "HSL" => Ok(Self::Hsl),
+            "HSV" => Ok(Self::Hsv),
+            "YUV" => Ok(Self::Yuv),
+            "CIELAB" | "LAB" => Ok(Self::Cielab),
+            "CIELUV" | "LUV" => Ok(Self::Cieluv),
+            "LCHAB" | "LCH" | "HCL" => Ok(Self::Lchab),
+            "LCHUV" => Ok(Self::Lchuv),
+            "OKLAB" => Ok(Self::Oklab),
+            "OKLCH" => Ok(Self::Oklch),
+            _ => Err(ParseColorSpaceError(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for ColorSpace {
+    type Error = ParseColorSpaceError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ColorSpace> for String {
+    fn from(space: ColorSpace) -> Self {
+        space.name().to_string()
+    }
+}
+
+/// Round-trip between 8-bit sRGB and a working color space.
+pub trait SpaceConversion {
+    fn to_space(&self, rgb: [u8; 3]) -> [f32; 3];
+    fn to_rgb8(&self, values: [f32; 3]) -> [u8; 3];
+}
+
+impl SpaceConversion for ColorSpace {
+    fn to_space(&self, rgb: [u8; 3]) -> [f32; 3] {
+        match self {
+            Self::Rgb => rgb.map(|c| c as f32),
+            Self::Hsl => rgb8_to_hsl(rgb),
+            Self::Hsv => rgb8_to_hsv(rgb),
+            Self::Yuv => rgb8_to_yuv(rgb),
+            Self::Cielab => rgb8_to_lab(rgb),
+            Self::Cieluv => rgb8_to_luv(rgb),
+            Self::Lchab => rgb8_to_lchab(rgb),
+            Self::Lchuv => rgb8_to_lchuv(rgb),
+            Self::Oklab => rgb8_to_oklab(rgb),
+            Self::Oklch => rgb8_to_oklch(rgb),
+        }
+    }
+
+    fn to_rgb8(&self, values: [f32; 3]) -> [u8; 3] {
+        match self {
+            Self::Rgb => values.map(|c| c.round().clamp(0.0, 255.0) as u8),
+            Self::Hsl => hsl_to_rgb8(values),
+            Self::Hsv => hsv_to_rgb8(values),
+            Self::Yuv => yuv_to_rgb8(values),
+            Self::Cielab => lab_to_rgb8(values),
+            Self::Cieluv => luv_to_rgb8(values),
+            Self::Lchab => lchab_to_rgb8(values),
+            Self::Lchuv => lchuv_to_rgb8(values),
+            Self::Oklab => oklab_to_rgb8(values),
+            Self::Oklch => oklch_to_rgb8(values),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -392,4 +694,102 @@ mod tests {
         assert!((lab[1] - 80.09).abs() < 0.5);
         assert!((lab[2] - 67.20).abs() < 0.5);
     }
+
+    #[test]
+    fn oklab_round_trip_and_reference() {
+        let samples = [[255, 0, 0], [12, 200, 64], [18, 42, 200], [0, 0, 0]];
+        for rgb in samples {
+            assert_rgb_close(rgb, oklab_to_rgb8(rgb8_to_oklab(rgb)), 1);
+            assert_rgb_close(rgb, oklch_to_rgb8(rgb8_to_oklch(rgb)), 1);
+        }
+        // Reference values from Ottosson's post
+        let white = rgb8_to_oklab([255, 255, 255]);
+        assert!((white[0] - 1.0).abs() < 1e-3);
+        assert!(white[1].abs() < 1e-3 && white[2].abs() < 1e-3);
+        let red = rgb8_to_oklch([255, 0, 0]);
+        assert!((red[0] - 0.628).abs() < 2e-3);
+        assert!((red[1] - 0.258).abs() < 2e-3);
+        assert!((red[2] - 29.23).abs() < 0.2);
+    }
+
+    #[test]
+    fn lch_hue_is_normalized() {
+        let lch = lab_to_lch([50.0, 0.0, -20.0]);
+        assert!((lch[1] - 20.0).abs() < 1e-4);
+        assert!((lch[2] - 270.0).abs() < 1e-3);
+        assert_eq!(lab_to_lch([50.0, 0.0, 0.0])[2], 0.0);
+    }
+
+    #[test]
+    fn lchab_matches_lab_polar_form() {
+        let lch = rgb8_to_lchab([255, 0, 0]);
+        assert!((lch[0] - 53.24).abs() < 0.2);
+        assert!((lch[1] - 104.55).abs() < 0.5);
+        assert!((lch[2] - 40.0).abs() < 0.5);
+        assert_eq!("hcl".parse::<ColorSpace>().unwrap(), ColorSpace::Lchab);
+    }
+
+    #[test]
+    fn ciede2000_matches_sharma_reference_pairs() {
+        // Pairs 1, 7, 17 and 25 of Sharma, Wu & Dalal's test data
+        let pairs = [
+            ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+            ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+            ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+            (
+                [60.2574, -34.0099, 36.2677],
+                [60.4626, -34.1751, 39.4387],
+                1.2644,
+            ),
+        ];
+        for (lab1, lab2, want) in pairs {
+            let got = delta_e_ciede2000(lab1, lab2);
+            assert!((got - want).abs() < 1e-3, "{lab1:?} {lab2:?}: {got}");
+            assert!((delta_e_ciede2000(lab2, lab1) - want).abs() < 1e-3);
+        }
+        assert_eq!(
+            DeltaE::Cie76.between([50.0, 3.0, 0.0], [50.0, 0.0, 4.0]),
+            5.0
+        );
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let rgb = [215, 38, 61];
+        assert_eq!(hex_to_rgb(&rgb_to_hex(rgb)), Some(rgb));
+        assert_eq!(hex_to_rgb("D7263D"), Some(rgb));
+        for bad in ["", "#fff", "#12345g", "#1234567", "#+12345"] {
+            assert_eq!(hex_to_rgb(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn color_space_parse_and_display() {
+        assert_eq!("lab".parse::<ColorSpace>().unwrap(), ColorSpace::Cielab);
+        assert_eq!(" Luv ".parse::<ColorSpace>().unwrap(), ColorSpace::Cieluv);
+        assert!("XYZ".parse::<ColorSpace>().is_err());
+        for space in ColorSpace::ALL {
+            assert_eq!(space.to_string().parse::<ColorSpace>().unwrap(), space);
+        }
+    }
+
+    #[test]
+    fn color_space_serde_uses_canonical_names() {
+        let json = serde_json::to_string(&ColorSpace::Cielab).unwrap();
+        assert_eq!(json, "\"CIELAB\"");
+        let parsed: ColorSpace = serde_json::from_str("\"hsv\"").unwrap();
+        assert_eq!(parsed, ColorSpace::Hsv);
+        assert!(serde_json::from_str::<ColorSpace>("\"XYZ\"").is_err());
+    }
+
+    #[test]
+    fn every_space_round_trips() {
+        let samples = [[255, 0, 0], [12, 200, 64], [18, 42, 200], [240, 240, 240]];
+        for space in ColorSpace::ALL {
+            for rgb in samples {
+                let back = space.to_rgb8(space.to_space(rgb));
+                assert_rgb_close(rgb, back, 3);
+            }
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
@@ -14,10 +15,29 @@ pub enum SamplingError {
     Io(#[from] std::io::Error),
     #[error("failed to decode image: {0}")]
     Decode(#[from] image::ImageError),
+    #[error("invalid pixel buffer: {0}")]
+    Buffer(String),
+    #[error("histogram bits must be between 1 and 8, got {0}")]
+    HistogramBits(u8),
+    #[error("pixel positions need reservoir sampling; histogram buckets span the whole image")]
+    PositionsInHistogram,
 }
 
 pub type Result<T> = std::result::Result<T, SamplingError>;
 
+/// How pixels become samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleMode {
+    /// Every `stride`-th pixel, reservoir-capped at `max_samples`; depends on `seed`.
+    #[default]
+    Reservoir,
+    /// Every pixel, deduplicated into a color histogram keyed on the top `bits`
+    /// of each channel (8 = exact colors, 5 or 6 = coarser buckets). Each bucket
+    /// reports the mean color of its pixels. `stride`, `max_samples`, `seed` and
+    /// `max_dimension` are ignored, so counts are of the full-resolution image.
+    Histogram { bits: u8 },
+}
+
 #[derive(Debug, Clone)]
 pub struct SampleParams {
     pub path: PathBuf,
@@ -26,6 +46,9 @@ pub struct SampleParams {
     pub max_samples: usize,
     pub max_dimension: Option<u32>,
     pub seed: u64,
+    pub mode: SampleMode,
+    /// Also report where each sample sits in the image (reservoir mode only).
+    pub positions: bool,
 }
 
 impl SampleParams {
@@ -37,6 +60,8 @@ impl SampleParams {
             max_samples: 300_000,
             max_dimension: Some(3200),
             seed: 1,
+            mode: SampleMode::Reservoir,
+            positions: false,
         }
     }
 }
@@ -44,10 +69,15 @@ impl SampleParams {
 #[derive(Debug, Serialize)]
 pub struct SampleResult {
     pub samples: Vec<[u8; 3]>,
-    pub samples_lab: Option<Vec<[f32; 3]>>,
+    /// Pixels per entry of `samples` in histogram mode; `None` for reservoir sampling.
+    pub counts: Option<Vec<u32>>,
+    /// Pixel centre of each sample as fractions of `width` and `height`, when
+    /// [`SampleParams::positions`] is set.
+    pub positions: Option<Vec<[f32; 2]>>,
     pub width: u32,
     pub height: u32,
     pub total_pixels: u64,
+    /// Pixels represented by `samples` (the sum of `counts` in histogram mode).
     pub sampled_pixels: usize,
     pub duration_ms: u128,
 }
@@ -65,18 +95,79 @@ pub fn prepare_samples(params: &SampleParams) -> Result<SampleResult> {
         .with_guessed_format()?
         .decode()?;
 
-    let rgb = to_rgb_with_downscale(img, params.max_dimension);
+    sample_image(img, params, start)
+}
+
+/// Sample an in-memory RGB or RGBA buffer (row-major, no padding).
+/// `params.path` is ignored; every other field behaves as in [`prepare_samples`].
+pub fn prepare_samples_from_buffer(
+    width: u32,
+    height: u32,
+    data: &[u8],
+    params: &SampleParams,
+) -> Result<SampleResult> {
+    let start = Instant::now();
+
+    let pixels = width as usize * height as usize;
+    if pixels == 0 {
+        return Err(SamplingError::Buffer("width/height must be > 0".into()));
+    }
+    let rgb = if data.len() == pixels * 3 {
+        RgbImage::from_raw(width, height, data.to_vec())
+    } else if data.len() == pixels * 4 {
+        let stripped = data
+            .chunks_exact(4)
+            .flat_map(|px| [px[0], px[1], px[2]])
+            .collect();
+        RgbImage::from_raw(width, height, stripped)
+    } else {
+        None
+    }
+    .ok_or_else(|| {
+        SamplingError::Buffer(format!(
+            "{} bytes is not a {width}x{height} RGB or RGBA buffer",
+            data.len()
+        ))
+    })?;
+
+    sample_image(DynamicImage::ImageRgb8(rgb), params, start)
+}
+
+fn sample_image(img: DynamicImage, params: &SampleParams, start: Instant) -> Result<SampleResult> {
+    if let SampleMode::Histogram { bits } = params.mode {
+        if !(1..=8).contains(&bits) {
+            return Err(SamplingError::HistogramBits(bits));
+        }
+        if params.positions {
+            return Err(SamplingError::PositionsInHistogram);
+        }
+    }
+
+    let limit = match params.mode {
+        SampleMode::Reservoir => params.max_dimension,
+        SampleMode::Histogram { .. } => None,
+    };
+    let rgb = to_rgb_with_downscale(img, limit);
     let (width, height) = rgb.dimensions();
-    let samples = sample_pixels(&rgb, params);
-    let samples_lab = samples
-        .iter()
-        .map(|rgb| crate::color::rgb8_to_lab(*rgb))
-        .collect::<Vec<_>>();
-    let sampled_pixels = samples.len();
+    let (samples, counts, positions) = match params.mode {
+        SampleMode::Reservoir => {
+            let (samples, positions) = sample_pixels(&rgb, params);
+            (samples, None, positions)
+        }
+        SampleMode::Histogram { bits } => {
+            let (colors, counts) = histogram_pixels(&rgb, params.min_lum, bits);
+            (colors, Some(counts), None)
+        }
+    };
+    let sampled_pixels = match &counts {
+        Some(counts) => counts.iter().map(|&c| c as usize).sum(),
+        None => samples.len(),
+    };
 
     Ok(SampleResult {
         samples,
-        samples_lab: Some(samples_lab),
+        counts,
+        positions,
         width,
         height,
         total_pixels: width as u64 * height as u64,
@@ -100,7 +191,8 @@ fn to_rgb_with_downscale(img: DynamicImage, limit: Option<u32>) -> RgbImage {
     rgb
 }
 
-fn sample_pixels(img: &RgbImage, params: &SampleParams) -> Vec<[u8; 3]> {
+/// Reservoir-sampled colors, plus their positions when `params.positions` is set.
+fn sample_pixels(img: &RgbImage, params: &SampleParams) -> (Vec<[u8; 3]>, Option<Vec<[f32; 2]>>) {
     let stride = params.stride.max(1) as usize;
     let min_lum = params.min_lum as f32;
     let max_samples = if params.max_samples == 0 {
@@ -110,8 +202,10 @@ fn sample_pixels(img: &RgbImage, params: &SampleParams) -> Vec<[u8; 3]> {
     };
 
     let (width, height) = img.dimensions();
-    let mut samples: Vec<[u8; 3]> =
-        Vec::with_capacity(max_samples.min((width as usize) * (height as usize)));
+    let capacity = max_samples.min((width as usize) * (height as usize));
+    let mut samples: Vec<[u8; 3]> = Vec::with_capacity(capacity);
+    let mut positions: Option<Vec<[f32; 2]>> =
+        params.positions.then(|| Vec::with_capacity(capacity));
 
     let mut rng = SmallRng::seed_from_u64(params.seed);
     let mut seen = 0_usize;
@@ -120,23 +214,68 @@ fn sample_pixels(img: &RgbImage, params: &SampleParams) -> Vec<[u8; 3]> {
         for x in (0..width as usize).step_by(stride) {
             let pixel = img.get_pixel(x as u32, y as u32);
             let [r, g, b] = pixel.0;
-            let lum = LUMA_R * (r as f32) + LUMA_G * (g as f32) + LUMA_B * (b as f32);
-            if lum < min_lum {
+            if luma(pixel.0) < min_lum {
                 continue;
             }
             seen += 1;
+            let at = [
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+            ];
             if samples.len() < max_samples {
                 samples.push([r, g, b]);
+                if let Some(positions) = &mut positions {
+                    positions.push(at);
+                }
             } else {
                 let idx = rng.gen_range(0..seen);
                 if idx < max_samples {
                     samples[idx] = [r, g, b];
+                    if let Some(positions) = &mut positions {
+                        positions[idx] = at;
+                    }
                 }
             }
         }
     }
 
-    samples
+    (samples, positions)
+}
+
+/// Unique (or bucketed) colors with their pixel counts, ordered by bucket key
+/// so the output does not depend on hash iteration order.
+fn histogram_pixels(img: &RgbImage, min_lum: u8, bits: u8) -> (Vec<[u8; 3]>, Vec<u32>) {
+    let shift = 8 - bits;
+    let min_lum = min_lum as f32;
+    let mut buckets: HashMap<u32, ([u64; 3], u32)> = HashMap::new();
+    for pixel in img.pixels() {
+        let [r, g, b] = pixel.0;
+        if luma(pixel.0) < min_lum {
+            continue;
+        }
+        let key = ((r >> shift) as u32) << 16 | ((g >> shift) as u32) << 8 | (b >> shift) as u32;
+        let (sum, count) = buckets.entry(key).or_default();
+        sum[0] += r as u64;
+        sum[1] += g as u64;
+        sum[2] += b as u64;
+        *count += 1;
+    }
+
+    let mut entries: Vec<_> = buckets.into_iter().collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+    entries
+        .into_iter()
+        .map(|(_, (sum, count))| {
+            let n = count as u64;
+            let mean = sum.map(|s| ((s + n / 2) / n) as u8);
+            (mean, count)
+        })
+        .unzip()
+}
+
+#[inline]
+fn luma([r, g, b]: [u8; 3]) -> f32 {
+    LUMA_R * (r as f32) + LUMA_G * (g as f32) + LUMA_B * (b as f32)
 }
 
 #[cfg(test)]
@@ -167,6 +306,8 @@ mod tests {
             max_samples: 10_000,
             max_dimension: None,
             seed: 42,
+            mode: SampleMode::Reservoir,
+            positions: false,
         };
 
         let result = prepare_samples(&params).expect("sample");
@@ -193,11 +334,110 @@ mod tests {
             max_samples: 50,
             max_dimension: None,
             seed: 7,
+            mode: SampleMode::Reservoir,
+            positions: false,
         };
         let result = prepare_samples(&params).expect("sample");
         assert_eq!(result.sampled_pixels, 50);
     }
 
+    #[test]
+    fn buffer_sampling_drops_alpha() {
+        let data: Vec<u8> = (0..16).flat_map(|_| [200u8, 100, 50, 0]).collect();
+        let params = SampleParams {
+            path: PathBuf::new(),
+            stride: 1,
+            min_lum: 0,
+            max_samples: 100,
+            max_dimension: None,
+            seed: 3,
+            mode: SampleMode::Reservoir,
+            positions: false,
+        };
+        let result = prepare_samples_from_buffer(4, 4, &data, &params).expect("sample");
+        assert_eq!(result.sampled_pixels, 16);
+        assert!(result.samples.iter().all(|&rgb| rgb == [200, 100, 50]));
+
+        let err = prepare_samples_from_buffer(4, 4, &data[..30], &params);
+        assert!(matches!(err, Err(SamplingError::Buffer(_))));
+    }
+
+    #[test]
+    fn histogram_counts_every_pixel() {
+        let mut img = RgbImage::new(8, 8);
+        for (x, _, pixel) in img.enumerate_pixels_mut() {
+            *pixel = if x < 2 {
+                Rgb([200, 10, 10])
+            } else if x < 4 {
+                Rgb([201, 11, 10])
+            } else {
+                Rgb([10, 10, 200])
+            };
+        }
+        let data = img.into_raw();
+        let mut params = SampleParams {
+            path: PathBuf::new(),
+            stride: 4,
+            min_lum: 0,
+            max_samples: 2,
+            // Ignored: histograms count the full-resolution image
+            max_dimension: Some(4),
+            seed: 1,
+            mode: SampleMode::Histogram { bits: 8 },
+            positions: false,
+        };
+        let exact = prepare_samples_from_buffer(8, 8, &data, &params).expect("sample");
+        assert_eq!(exact.samples.len(), 3);
+        assert_eq!(exact.sampled_pixels, 64);
+        assert_eq!((exact.width, exact.height), (8, 8));
+        let counts = exact.counts.expect("counts");
+        assert_eq!(counts.iter().sum::<u32>(), 64);
+
+        params.mode = SampleMode::Histogram { bits: 5 };
+        let coarse = prepare_samples_from_buffer(8, 8, &data, &params).expect("sample");
+        assert_eq!(coarse.samples, vec![[10, 10, 200], [201, 11, 10]]);
+        assert_eq!(coarse.counts, Some(vec![32, 32]));
+
+        params.mode = SampleMode::Histogram { bits: 0 };
+        let err = prepare_samples_from_buffer(8, 8, &data, &params);
+        assert!(matches!(err, Err(SamplingError::HistogramBits(0))));
+    }
+
+    #[test]
+    fn positions_follow_their_samples() {
+        // Left half red, right half blue
+        let mut img = RgbImage::new(8, 4);
+        for (x, _, pixel) in img.enumerate_pixels_mut() {
+            *pixel = if x < 4 {
+                Rgb([200, 10, 10])
+            } else {
+                Rgb([10, 10, 200])
+            };
+        }
+        let data = img.into_raw();
+        let mut params = SampleParams {
+            path: PathBuf::new(),
+            stride: 1,
+            min_lum: 0,
+            max_samples: 10,
+            max_dimension: None,
+            seed: 5,
+            mode: SampleMode::Reservoir,
+            positions: true,
+        };
+        let result = prepare_samples_from_buffer(8, 4, &data, &params).expect("sample");
+        let positions = result.positions.expect("positions");
+        assert_eq!(positions.len(), result.samples.len());
+        for (rgb, [x, y]) in result.samples.iter().zip(&positions) {
+            assert_eq!(*x < 0.5, rgb[0] == 200, "{rgb:?} at {x}");
+            assert!((0.0..1.0).contains(y));
+        }
+
+        params.mode = SampleMode::Histogram { bits: 8 };
+        let err = prepare_samples_from_buffer(8, 4, &data, &params);
+        assert!(matches!(err, Err(SamplingError::PositionsInHistogram)));
+    }
+
     #[test]
     fn downscale_limits_dimensions() {
         let mut img = RgbImage::new(4000, 1000);
@@ -212,6 +452,8 @@ mod tests {
             max_samples: 10_000,
             max_dimension: Some(1024),
             seed: 1,
+            mode: SampleMode::Reservoir,
+            positions: false,
         };
         let result = prepare_samples(&params).expect("sample");
         assert!(result.width <= 1024 && result.height <= 1024);
@@ -0,0 +1,344 @@
+//! Choosing k: sweep a range of k, warm-starting each run from the previous
+//! centroids plus the worst-fit point, and score every k with one criterion.
+
+use std::ops::RangeInclusive;
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use super::{
+    best_centroid, run_kmeans_soa, CentroidsSoa, EmptyClusterStrategy, KMeansConfig, KMeansError,
+    KMeansModel, KMeansResult, Metric, PointsSoa, Result,
+};
+
+/// How [`auto_k`] scores each candidate k.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum KCriterion {
+    /// Knee of the inertia curve: the k furthest below the straight line
+    /// between the first and last inertia of the sweep. Higher is better.
+    Elbow,
+    /// Mean silhouette over at most `sample` points (seeded by `cfg.seed`).
+    /// Higher is better; undefined for k = 1.
+    Silhouette { sample: usize },
+    /// Davies–Bouldin index over all points. Lower is better; undefined for k = 1.
+    DaviesBouldin,
+}
+
+impl KCriterion {
+    fn prefers_higher(self) -> bool {
+        !matches!(self, KCriterion::DaviesBouldin)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KScore {
+    pub k: usize,
+    pub inertia: f32,
+    /// `None` where the criterion is undefined (k = 1 for silhouette and Davies–Bouldin).
+    pub score: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoKResult {
+    pub k: usize,
+    /// One entry per k in the sweep, in increasing k.
+    pub curve: Vec<KScore>,
+    /// The clustering for the chosen k.
+    pub result: KMeansResult,
+}
+
+/// Run k-means for every k in `k_range` (clamped to the number of points) and
+/// pick the best k by `criterion`; ties go to the smaller k. `cfg.k` is ignored;
+/// `cfg.warm_start`, if set, seeds the first k and must match `k_range.start()`.
+pub fn auto_k(
+    dataset: &PointsSoa,
+    k_range: RangeInclusive<usize>,
+    criterion: KCriterion,
+    cfg: &KMeansConfig,
+) -> Result<AutoKResult> {
+    let (k_min, k_max) = (*k_range.start(), (*k_range.end()).min(dataset.len()));
+    if k_min == 0 || k_min > k_max {
+        return Err(KMeansError::InvalidConfig(
+            "k range must be non-empty, start at 1 and fit the data",
+        ));
+    }
+    let metric = Metric::new(cfg)?;
+
+    let mut runs: Vec<KMeansResult> = Vec::with_capacity(k_max - k_min + 1);
+    let mut warm_start = cfg.warm_start.clone();
+    for k in k_min..=k_max {
+        let run_cfg = KMeansConfig {
+            k,
+            warm_start: warm_start.take(),
+            return_labels: false,
+            // Each k warm-starts the next, so every run must keep all k centroids
+            on_empty: match cfg.on_empty {
+                EmptyClusterStrategy::Drop => EmptyClusterStrategy::FarthestPoint,
+                other => other,
+            },
+            ..cfg.clone()
+        };
+        let result = run_kmeans_soa(dataset, &run_cfg)?;
+        if k < k_max {
+            let mut next = result.centroids.clone();
+            next.push(worst_fit_point(dataset, &result.centroids, &metric));
+            warm_start = Some(next);
+        }
+        runs.push(result);
+    }
+
+    let scores: Vec<Option<f32>> = match criterion {
+        KCriterion::Elbow => elbow_scores(&runs),
+        KCriterion::Silhouette { sample } => runs
+            .iter()
+            .map(|run| silhouette(dataset, &run.centroids, &metric, sample, cfg.seed))
+            .collect(),
+        KCriterion::DaviesBouldin => runs
+            .iter()
+            .map(|run| davies_bouldin(dataset, &run.centroids, &metric))
+            .collect(),
+    };
+
+    let better = |a: f32, b: f32| {
+        if criterion.prefers_higher() {
+            a > b
+        } else {
+            a < b
+        }
+    };
+    let mut chosen = 0;
+    for (idx, score) in scores.iter().enumerate() {
+        if let Some(score) = *score {
+            match scores[chosen] {
+                Some(best) if !better(score, best) => {}
+                _ => chosen = idx,
+            }
+        }
+    }
+
+    let curve = runs
+        .iter()
+        .zip(scores.iter())
+        .enumerate()
+        .map(|(idx, (run, score))| KScore {
+            k: k_min + idx,
+            inertia: run.inertia,
+            score: *score,
+        })
+        .collect();
+    let mut result = runs.swap_remove(chosen);
+    if cfg.return_labels {
+        let model = KMeansModel {
+            centroids: CentroidsSoa::from_vec(&result.centroids),
+            metric,
+        };
+        result.labels = Some(model.predict_soa(dataset));
+    }
+
+    Ok(AutoKResult {
+        k: k_min + chosen,
+        curve,
+        result,
+    })
+}
+
+/// The point contributing the most weighted distance to its nearest centroid;
+/// a deterministic choice for the extra centroid when moving from k to k + 1.
+fn worst_fit_point(dataset: &PointsSoa, centroids: &[[f32; 3]], metric: &Metric) -> [f32; 3] {
+    let soa = CentroidsSoa::from_vec(centroids);
+    let (idx, _) = (0..dataset.len())
+        .into_par_iter()
+        .map(|idx| {
+            let (px, py, pz) = dataset.component_tuple(idx);
+            let (_, dist) = best_centroid(px, py, pz, &soa, metric);
+            (idx, dist * dataset.weight(idx))
+        })
+        .reduce(
+            || (usize::MAX, f32::MIN),
+            |a, b| {
+                if b.1 > a.1 || (b.1 == a.1 && b.0 < a.0) {
+                    b
+                } else {
+                    a
+                }
+            },
+        );
+    let (x, y, z) = dataset.component_tuple(idx);
+    [x, y, z]
+}
+
+fn elbow_scores(runs: &[KMeansResult]) -> Vec<Option<f32>> {
+    let (first, last) = (runs[0].inertia, runs[runs.len() - 1].inertia);
+    let span = first - last;
+    if runs.len() < 3 || span <= 0.0 {
+        return vec![Some(0.0); runs.len()];
+    }
+    let steps = (runs.len() - 1) as f32;
+    runs.iter()
+        .enumerate()
+        .map(|(idx, run)| {
+            // Chord from (0, 1) to (1, 0) in normalised (k, inertia) space
+            let x = idx as f32 / steps;
+            let y = (run.inertia - last) / span;
+            Some((1.0 - x) - y)
+        })
+        .collect()
+}
+
+fn silhouette(
+    dataset: &PointsSoa,
+    centroids: &[[f32; 3]],
+    metric: &Metric,
+    sample: usize,
+    seed: u64,
+) -> Option<f32> {
+    let k = centroids.len();
+    if k < 2 {
+        return None;
+    }
+    let n = dataset.len();
+    let indices: Vec<usize> = if sample == 0 || sample >= n {
+        (0..n).collect()
+    } else {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        (0..sample).map(|_| rng.gen_range(0..n)).collect()
+    };
+    let soa = CentroidsSoa::from_vec(centroids);
+    let labels: Vec<usize> = indices
+        .iter()
+        .map(|&idx| {
+            let (px, py, pz) = dataset.component_tuple(idx);
+            best_centroid(px, py, pz, &soa, metric).0
+        })
+        .collect();
+
+    let per_point: Vec<(f64, f64)> = (0..indices.len())
+        .into_par_iter()
+        .map(|i| {
+            let (px, py, pz) = dataset.component_tuple(indices[i]);
+            let mut sums = vec![0.0f64; k];
+            let mut weights = vec![0.0f64; k];
+            for (j, &other) in indices.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let (ox, oy, oz) = dataset.component_tuple(other);
+                let w = dataset.weight(other) as f64;
+                sums[labels[j]] += w * metric.distance(px, py, pz, ox, oy, oz).sqrt() as f64;
+                weights[labels[j]] += w;
+            }
+            let own = labels[i];
+            let w = dataset.weight(indices[i]) as f64;
+            if weights[own] == 0.0 {
+                // Singleton clusters score 0 by convention
+                return (0.0, w);
+            }
+            let a = sums[own] / weights[own];
+            let b = (0..k)
+                .filter(|&c| c != own && weights[c] > 0.0)
+                .map(|c| sums[c] / weights[c])
+                .fold(f64::INFINITY, f64::min);
+            if !b.is_finite() {
+                return (0.0, w);
+            }
+            let s = (b - a) / a.max(b).max(f64::MIN_POSITIVE);
+            (w * s, w)
+        })
+        .collect();
+    // Sum in index order so the score does not depend on thread scheduling
+    let (total, weight) = per_point
+        .iter()
+        .fold((0.0, 0.0), |acc, &(s, w)| (acc.0 + s, acc.1 + w));
+    (weight > 0.0).then(|| (total / weight) as f32)
+}
+
+fn davies_bouldin(dataset: &PointsSoa, centroids: &[[f32; 3]], metric: &Metric) -> Option<f32> {
+    let k = centroids.len();
+    if k < 2 {
+        return None;
+    }
+    let soa = CentroidsSoa::from_vec(centroids);
+    let assigned: Vec<(usize, f32)> = (0..dataset.len())
+        .into_par_iter()
+        .map(|idx| {
+            let (px, py, pz) = dataset.component_tuple(idx);
+            best_centroid(px, py, pz, &soa, metric)
+        })
+        .collect();
+    let mut scatter_sum = vec![0.0f64; k];
+    let mut weight = vec![0.0f64; k];
+    for (idx, &(label, dist)) in assigned.iter().enumerate() {
+        let w = dataset.weight(idx) as f64;
+        scatter_sum[label] += w * dist.sqrt() as f64;
+        weight[label] += w;
+    }
+
+    let live: Vec<usize> = (0..k).filter(|&c| weight[c] > 0.0).collect();
+    if live.len() < 2 {
+        return None;
+    }
+    let scatter = |c: usize| scatter_sum[c] / weight[c];
+    let total: f64 = live
+        .iter()
+        .map(|&i| {
+            let [ix, iy, iz] = centroids[i];
+            live.iter()
+                .filter(|&&j| j != i)
+                .map(|&j| {
+                    let [jx, jy, jz] = centroids[j];
+                    let separation = metric.distance(ix, iy, iz, jx, jy, jz).sqrt() as f64;
+                    (scatter(i) + scatter(j)) / separation.max(f64::MIN_POSITIVE)
+                })
+                .fold(0.0, f64::max)
+        })
+        .sum();
+    Some((total / live.len() as f64) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn four_blobs() -> PointsSoa {
+        let mut points = Vec::new();
+        for i in 0..200 {
+            let t = (i % 10) as f32 * 0.1;
+            points.push([t, t, 0.0]);
+            points.push([20.0 + t, 0.0, t]);
+            points.push([0.0, 20.0 + t, t]);
+            points.push([20.0 + t, 20.0, t]);
+        }
+        PointsSoa::from_points(&points)
+    }
+
+    #[test]
+    fn every_criterion_finds_four_blobs() {
+        let dataset = four_blobs();
+        let cfg = KMeansConfig {
+            seed: 5,
+            ..KMeansConfig::default()
+        };
+        for criterion in [
+            KCriterion::Elbow,
+            KCriterion::Silhouette { sample: 300 },
+            KCriterion::DaviesBouldin,
+        ] {
+            let found = auto_k(&dataset, 1..=8, criterion, &cfg).expect("auto_k");
+            assert_eq!(found.k, 4, "{criterion:?}: {:?}", found.curve);
+            assert_eq!(found.curve.len(), 8);
+            assert_eq!(found.result.centroids.len(), 4);
+        }
+    }
+
+    #[test]
+    fn range_is_clamped_and_validated() {
+        let dataset = PointsSoa::from_points(&[[0.0; 3], [1.0; 3], [2.0; 3]]);
+        let cfg = KMeansConfig::default();
+        let found = auto_k(&dataset, 2..=10, KCriterion::DaviesBouldin, &cfg).expect("auto_k");
+        assert_eq!(found.curve.last().map(|s| s.k), Some(3));
+        assert!(auto_k(&dataset, 0..=3, KCriterion::Elbow, &cfg).is_err());
+    }
+}
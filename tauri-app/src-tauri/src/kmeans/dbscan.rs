@@ -0,0 +1,345 @@
+//! Density-based clustering that finds k on its own: DBSCAN over a grid of
+//! `eps`-sized cells, so the cost is one pass over the points plus 27 lookups
+//! per occupied cell rather than a neighbour search per point.
+
+use std::collections::BTreeMap;
+
+use super::{validate, DistanceMode, KMeansConfig, KMeansError, Metric, PointsSoa, Result};
+
+/// Label of points that belong to no cluster.
+pub const NOISE: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+pub struct DbscanConfig {
+    /// Neighbourhood radius in clustering-space units (ΔE76 in CIELAB), used
+    /// as the grid cell size. A cell's neighbourhood is itself plus the 26
+    /// cells around it, so points up to about `2·√3·eps` apart can link.
+    pub eps: f32,
+    /// Weight (points, or pixels for weighted input) a neighbourhood needs for
+    /// its centre cell to be a core cell. Clusters are connected core cells
+    /// plus the non-core cells touching them; everything else is noise.
+    pub min_weight: f32,
+    pub distance: DistanceMode,
+    /// Fill [`DbscanResult::labels`], with [`NOISE`] for unclustered points.
+    pub return_labels: bool,
+}
+
+impl Default for DbscanConfig {
+    fn default() -> Self {
+        Self {
+            eps: 4.0,
+            min_weight: 10.0,
+            distance: DistanceMode::Euclidean,
+            return_labels: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DbscanResult {
+    /// Clusters sorted by descending count.
+    pub centroids: Vec<[f32; 3]>,
+    /// Points per cluster; for weighted input, the rounded sum of member weights.
+    pub counts: Vec<usize>,
+    /// Points in no cluster, counted like `counts`.
+    pub noise: usize,
+    /// Weighted squared distance of every clustered point to its centroid.
+    pub inertia: f32,
+    /// Cluster index per input point, when [`DbscanConfig::return_labels`] is set.
+    pub labels: Option<Vec<u32>>,
+}
+
+pub fn run_dbscan(points: &[[f32; 3]], cfg: &DbscanConfig) -> Result<DbscanResult> {
+    let dataset = PointsSoa::from_points(points);
+    run_dbscan_soa(&dataset, cfg)
+}
+
+/// DBSCAN over deduplicated points where `weights[i]` is how many times
+/// `points[i]` occurs.
+pub fn run_dbscan_weighted(
+    points: &[[f32; 3]],
+    weights: &[f32],
+    cfg: &DbscanConfig,
+) -> Result<DbscanResult> {
+    let dataset = PointsSoa::from_weighted_points(points, weights)?;
+    run_dbscan_soa(&dataset, cfg)
+}
+
+pub fn run_dbscan_soa(dataset: &PointsSoa, cfg: &DbscanConfig) -> Result<DbscanResult> {
+    if !(cfg.eps > 0.0 && cfg.eps.is_finite()) {
+        return Err(KMeansError::InvalidConfig("eps must be finite and > 0"));
+    }
+    if !(cfg.min_weight >= 0.0 && cfg.min_weight.is_finite()) {
+        return Err(KMeansError::InvalidConfig(
+            "min_weight must be finite and >= 0",
+        ));
+    }
+    let metric_cfg = KMeansConfig {
+        k: 1,
+        distance: cfg.distance,
+        ..KMeansConfig::default()
+    };
+    validate(dataset, &metric_cfg)?;
+    let metric = Metric::new(&metric_cfg)?;
+    let grid = CellGrid::new(cfg.eps, &metric);
+
+    // Occupied cells in first-seen order
+    let mut cell_of_key: BTreeMap<[i64; 3], usize> = BTreeMap::new();
+    let mut keys: Vec<[i64; 3]> = Vec::new();
+    let mut cell_weight: Vec<f64> = Vec::new();
+    let point_cells: Vec<usize> = (0..dataset.len())
+        .map(|idx| {
+            let key = grid.key(dataset.component_tuple(idx));
+            let cell = *cell_of_key.entry(key).or_insert_with(|| {
+                keys.push(key);
+                cell_weight.push(0.0);
+                keys.len() - 1
+            });
+            cell_weight[cell] += dataset.weight(idx) as f64;
+            cell
+        })
+        .collect();
+
+    let neighbours: Vec<Vec<usize>> = keys
+        .iter()
+        .map(|key| grid.neighbours(key, &cell_of_key))
+        .collect();
+    let density: Vec<f64> = neighbours
+        .iter()
+        .map(|cells| cells.iter().map(|&c| cell_weight[c]).sum())
+        .collect();
+    let core: Vec<bool> = density
+        .iter()
+        .map(|&d| d >= cfg.min_weight as f64)
+        .collect();
+
+    // Connected components of core cells
+    let mut cell_cluster = vec![NOISE; keys.len()];
+    let mut clusters = 0u32;
+    for start in 0..keys.len() {
+        if !core[start] || cell_cluster[start] != NOISE {
+            continue;
+        }
+        cell_cluster[start] = clusters;
+        let mut stack = vec![start];
+        while let Some(cell) = stack.pop() {
+            for &next in &neighbours[cell] {
+                if core[next] && cell_cluster[next] == NOISE {
+                    cell_cluster[next] = clusters;
+                    stack.push(next);
+                }
+            }
+        }
+        clusters += 1;
+    }
+    // Border cells join their densest core neighbour; ties go to the earliest cell
+    for cell in 0..keys.len() {
+        if core[cell] {
+            continue;
+        }
+        let owner = neighbours[cell]
+            .iter()
+            .copied()
+            .filter(|&c| core[c])
+            .max_by(|&a, &b| density[a].total_cmp(&density[b]).then(b.cmp(&a)));
+        if let Some(owner) = owner {
+            cell_cluster[cell] = cell_cluster[owner];
+        }
+    }
+
+    // [weight, Σx, Σy, Σz, Σsin, Σcos] per cluster
+    let mut sums = vec![[0.0f64; 6]; clusters as usize];
+    let mut noise = 0.0f64;
+    for (idx, &cell) in point_cells.iter().enumerate() {
+        let w = dataset.weight(idx) as f64;
+        let Some(sum) = sums.get_mut(cell_cluster[cell] as usize) else {
+            noise += w;
+            continue;
+        };
+        let (px, py, pz) = dataset.component_tuple(idx);
+        sum[0] += w;
+        sum[1] += w * px as f64;
+        sum[2] += w * py as f64;
+        sum[3] += w * pz as f64;
+        if let Some(axis) = metric.circular_axis {
+            let (sin, cos) = ([px, py, pz][axis] * metric.angle_scale).sin_cos();
+            sum[4] += w * sin as f64;
+            sum[5] += w * cos as f64;
+        }
+    }
+
+    // Heaviest cluster first; ties keep discovery order
+    let mut order: Vec<usize> = (0..sums.len()).collect();
+    order.sort_by(|&a, &b| sums[b][0].total_cmp(&sums[a][0]).then(a.cmp(&b)));
+    let mut rank = vec![0u32; sums.len()];
+    for (new, &old) in order.iter().enumerate() {
+        rank[old] = new as u32;
+    }
+    let centroids: Vec<[f32; 3]> = order
+        .iter()
+        .map(|&c| {
+            let s = &sums[c];
+            let weight = s[0].max(f64::MIN_POSITIVE);
+            let mut centroid = [
+                (s[1] / weight) as f32,
+                (s[2] / weight) as f32,
+                (s[3] / weight) as f32,
+            ];
+            if let Some(axis) = metric.circular_axis {
+                centroid[axis] = metric.circular_mean(s[4] as f32, s[5] as f32);
+            }
+            centroid
+        })
+        .collect();
+
+    let labels: Vec<u32> = point_cells
+        .iter()
+        .map(|&cell| match cell_cluster[cell] {
+            NOISE => NOISE,
+            cluster => rank[cluster as usize],
+        })
+        .collect();
+    let mut inertia = 0.0f64;
+    for (idx, &label) in labels.iter().enumerate() {
+        if label == NOISE {
+            continue;
+        }
+        let (px, py, pz) = dataset.component_tuple(idx);
+        let [cx, cy, cz] = centroids[label as usize];
+        let dist = metric.distance(px, py, pz, cx, cy, cz);
+        inertia += dataset.weight(idx) as f64 * dist as f64;
+    }
+
+    Ok(DbscanResult {
+        counts: order.iter().map(|&c| sums[c][0].round() as usize).collect(),
+        centroids,
+        noise: noise.round() as usize,
+        inertia: inertia as f32,
+        labels: cfg.return_labels.then_some(labels),
+    })
+}
+
+/// Cubic cells of side `eps`; on a circular axis the period is split into a
+/// whole number of cells (at least `eps` wide) and indices wrap around.
+struct CellGrid {
+    size: [f32; 3],
+    circular: Option<(usize, i64)>,
+}
+
+impl CellGrid {
+    fn new(eps: f32, metric: &Metric) -> Self {
+        let mut size = [eps; 3];
+        let circular = metric.circular_axis.map(|axis| {
+            let bins = ((metric.period / eps).floor() as i64).max(1);
+            size[axis] = metric.period / bins as f32;
+            (axis, bins)
+        });
+        Self { size, circular }
+    }
+
+    fn key(&self, point: (f32, f32, f32)) -> [i64; 3] {
+        let p = [point.0, point.1, point.2];
+        std::array::from_fn(|axis| match self.circular {
+            Some((circular, bins)) if circular == axis => {
+                let period = self.size[axis] * bins as f32;
+                ((p[axis].rem_euclid(period) / self.size[axis]) as i64).min(bins - 1)
+            }
+            _ => (p[axis] / self.size[axis]).floor() as i64,
+        })
+    }
+
+    /// Occupied cells among `key` and its 26 neighbours, including `key` itself.
+    fn neighbours(&self, key: &[i64; 3], cells: &BTreeMap<[i64; 3], usize>) -> Vec<usize> {
+        let mut found = Vec::with_capacity(27);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let mut next = [key[0] + dx, key[1] + dy, key[2] + dz];
+                    if let Some((axis, bins)) = self.circular {
+                        next[axis] = next[axis].rem_euclid(bins);
+                    }
+                    if let Some(&cell) = cells.get(&next) {
+                        found.push(cell);
+                    }
+                }
+            }
+        }
+        // Fewer than three hue cells wrap onto the same neighbour twice
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_k_and_leaves_stragglers_as_noise() {
+        let mut points = Vec::new();
+        for i in 0..300 {
+            let t = (i % 10) as f32 * 0.3;
+            points.push([20.0 + t, 10.0, 10.0 - t]);
+            points.push([60.0 - t, -30.0 + t, 40.0]);
+            if i < 100 {
+                points.push([80.0, 40.0 + t, -20.0]);
+            }
+        }
+        let stragglers = [[0.0, 90.0, 90.0], [100.0, -90.0, 0.0], [40.0, 60.0, -60.0]];
+        points.extend(stragglers);
+        let cfg = DbscanConfig {
+            return_labels: true,
+            ..DbscanConfig::default()
+        };
+        let result = run_dbscan(&points, &cfg).expect("dbscan");
+        assert_eq!(result.counts, vec![300, 300, 100]);
+        assert_eq!(result.noise, 3);
+        assert!((result.centroids[2][1] - 41.35).abs() < 1e-3, "{result:?}");
+        let labels = result.labels.expect("labels");
+        assert!(labels[labels.len() - 3..].iter().all(|&l| l == NOISE));
+        assert_eq!(labels[0], labels[3]);
+
+        // A higher bar turns the smallest cluster into noise as well
+        let strict = DbscanConfig {
+            min_weight: 200.0,
+            ..DbscanConfig::default()
+        };
+        let result = run_dbscan(&points, &strict).expect("dbscan");
+        assert_eq!(result.counts, vec![300, 300]);
+        assert_eq!(result.noise, 103);
+    }
+
+    #[test]
+    fn hue_clusters_wrap_around_zero() {
+        let mut points = Vec::new();
+        for i in 0..50 {
+            let t = (i % 5) as f32;
+            points.push([358.0 + t * 0.4 - 1.0, 0.5, 0.5]);
+            points.push([t * 0.4 + 1.0, 0.5, 0.5]);
+        }
+        let cfg = DbscanConfig {
+            eps: 3.0,
+            min_weight: 5.0,
+            distance: DistanceMode::Circular {
+                axis: 0,
+                period: 360.0,
+            },
+            ..DbscanConfig::default()
+        };
+        let result = run_dbscan(&points, &cfg).expect("dbscan");
+        assert_eq!(result.counts, vec![100]);
+        let hue = result.centroids[0][0];
+        assert!(!(1.0..=359.0).contains(&hue), "{hue}");
+
+        let linear = DbscanConfig {
+            distance: DistanceMode::Euclidean,
+            ..cfg
+        };
+        assert_eq!(
+            run_dbscan(&points, &linear).expect("dbscan").counts.len(),
+            2
+        );
+        assert!(run_dbscan(&points, &DbscanConfig { eps: 0.0, ..linear }).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! What a Lloyd/Hamerly iteration does with clusters that lost every point.
+
+use rand::{rngs::SmallRng, Rng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{farthest_points, nearest_centroid, CentroidsSoa, Metric, PointsSoa};
+
+/// Points per chunk when accumulating cluster spreads; fixed so the f64 sums
+/// do not depend on the thread count.
+const SPREAD_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EmptyClusterStrategy {
+    /// Move the centroid to a uniformly random point.
+    #[default]
+    Random,
+    /// Move the centroid to the point with the largest weighted distance to
+    /// its nearest centroid.
+    FarthestPoint,
+    /// Split the cluster with the largest inertia in two, one standard
+    /// deviation either side of its centroid along its widest axis.
+    SplitLargest,
+    /// Remove the centroid; the result may have fewer than k clusters.
+    Drop,
+}
+
+/// Which strategy handled empty clusters in a run, and how often.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmptyClusterReport {
+    pub strategy: EmptyClusterStrategy,
+    /// Empty clusters summed over all iterations.
+    pub occurrences: usize,
+}
+
+/// Re-place the centroids at `empty` (ascending) after the others have been
+/// updated; the first `pinned` centroids are never moved. `Drop` is left to
+/// the caller since it renumbers clusters.
+pub(super) fn refill(
+    dataset: &PointsSoa,
+    centroids: &mut CentroidsSoa,
+    empty: &[usize],
+    pinned: usize,
+    strategy: EmptyClusterStrategy,
+    metric: &Metric,
+    rng: &mut SmallRng,
+) {
+    match strategy {
+        EmptyClusterStrategy::Random => {
+            for &idx in empty {
+                let rand_idx = rng.gen_range(0..dataset.len());
+                centroids.set_from_soa(idx, dataset, rand_idx);
+            }
+        }
+        EmptyClusterStrategy::FarthestPoint => {
+            let live = centroids.select(&live_indices(centroids, empty));
+            let picks = farthest_points(dataset, &live, empty.len(), metric);
+            for (&idx, pick) in empty.iter().zip(picks) {
+                centroids.set_from_soa(idx, dataset, pick);
+            }
+        }
+        EmptyClusterStrategy::SplitLargest => {
+            split_largest(dataset, centroids, empty, pinned, metric)
+        }
+        EmptyClusterStrategy::Drop => {}
+    }
+}
+
+fn live_indices(centroids: &CentroidsSoa, empty: &[usize]) -> Vec<usize> {
+    (0..centroids.len())
+        .filter(|idx| !empty.contains(idx))
+        .collect()
+}
+
+/// Per live cluster: weight, inertia and per-axis weighted squared deviation.
+#[derive(Clone, Default)]
+struct Spread {
+    weight: f64,
+    inertia: f64,
+    axis: [f64; 3],
+}
+
+fn split_largest(
+    dataset: &PointsSoa,
+    centroids: &mut CentroidsSoa,
+    empty: &[usize],
+    pinned: usize,
+    metric: &Metric,
+) {
+    let live = live_indices(centroids, empty);
+    let live_soa = centroids.select(&live);
+
+    let chunk_spreads: Vec<Vec<Spread>> = (0..dataset.len().div_ceil(SPREAD_CHUNK))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut spreads = vec![Spread::default(); live.len()];
+            let end = ((chunk + 1) * SPREAD_CHUNK).min(dataset.len());
+            for idx in chunk * SPREAD_CHUNK..end {
+                let (px, py, pz) = dataset.component_tuple(idx);
+                let w = dataset.weight(idx) as f64;
+                let (label, dist) = nearest_centroid(dataset, idx, &live_soa, metric);
+                let (cx, cy, cz) = live_soa.component_tuple(label);
+                let spread = &mut spreads[label];
+                spread.weight += w;
+                spread.inertia += w * dist as f64;
+                for (axis, (p, c)) in [(px, cx), (py, cy), (pz, cz)].into_iter().enumerate() {
+                    let d = metric.axis_delta(axis, c, p) as f64;
+                    spread.axis[axis] += w * d * d;
+                }
+            }
+            spreads
+        })
+        .collect();
+    let mut spreads = vec![Spread::default(); live.len()];
+    for chunk in chunk_spreads {
+        for (total, part) in spreads.iter_mut().zip(chunk) {
+            total.weight += part.weight;
+            total.inertia += part.inertia;
+            for axis in 0..3 {
+                total.axis[axis] += part.axis[axis];
+            }
+        }
+    }
+
+    for &target in empty {
+        // Ties go to the lowest index
+        let Some((slot, largest)) = spreads
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.inertia > 0.0 && s.weight > 0.0)
+            .max_by(|a, b| a.1.inertia.total_cmp(&b.1.inertia).then(b.0.cmp(&a.0)))
+        else {
+            // Every cluster is a single repeated point; nothing to split
+            return;
+        };
+        let axis = (0..3)
+            .max_by(|&a, &b| largest.axis[a].total_cmp(&largest.axis[b]).then(b.cmp(&a)))
+            .expect("three axes");
+        let var = largest.axis[axis];
+        let sigma = (var / largest.weight).sqrt() as f32;
+
+        let source = live[slot];
+        let mut moved = [
+            centroids.cx[source],
+            centroids.cy[source],
+            centroids.cz[source],
+        ];
+        let mut split = moved;
+        // A pinned source stays put and only the new half moves out
+        if source >= pinned {
+            moved[axis] = metric.offset(axis, moved[axis], -sigma);
+        }
+        split[axis] = metric.offset(axis, split[axis], sigma);
+        for (idx, [x, y, z]) in [(source, moved), (target, split)] {
+            centroids.cx[idx] = x;
+            centroids.cy[idx] = y;
+            centroids.cz[idx] = z;
+        }
+        // In a spatial run both halves start where the source cluster sits
+        if let Some(positions) = centroids.positions.as_mut() {
+            positions[target] = positions[source];
+        }
+        // Assume an even split so a second empty cluster goes elsewhere if needed
+        let half = Spread {
+            weight: largest.weight / 2.0,
+            inertia: largest.inertia / 2.0,
+            axis: largest.axis.map(|v| v / 2.0),
+        };
+        spreads[slot] = half;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmeans::{run_kmeans, AssignmentMode, KMeansConfig};
+
+    /// A tight blob, a wide blob, and a warm start whose third centroid is
+    /// too far away to win any point on the first iteration.
+    fn stranded_centroid(on_empty: EmptyClusterStrategy) -> (Vec<[f32; 3]>, KMeansConfig) {
+        let mut points = Vec::new();
+        for i in 0..100 {
+            let t = (i % 10) as f32;
+            points.push([t * 0.1, 0.0, 0.0]);
+            points.push([50.0 + t * 2.0, 0.0, 0.0]);
+        }
+        let cfg = KMeansConfig {
+            k: 3,
+            warm_start: Some(vec![
+                [0.45, 0.0, 0.0],
+                [59.0, 0.0, 0.0],
+                [1000.0, 1000.0, 1000.0],
+            ]),
+            on_empty,
+            ..KMeansConfig::default()
+        };
+        (points, cfg)
+    }
+
+    #[test]
+    fn refilling_strategies_keep_k_and_report_themselves() {
+        for strategy in [
+            EmptyClusterStrategy::Random,
+            EmptyClusterStrategy::FarthestPoint,
+            EmptyClusterStrategy::SplitLargest,
+        ] {
+            let (points, cfg) = stranded_centroid(strategy);
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            assert_eq!(result.centroids.len(), 3, "{strategy:?}");
+            assert_eq!(
+                result.empty_clusters.map(|r| r.strategy),
+                Some(strategy),
+                "{strategy:?}"
+            );
+            assert!(
+                result.counts.iter().all(|&c| c > 0),
+                "{strategy:?}: {:?}",
+                result.counts
+            );
+        }
+
+        // Both targeted strategies leave the tight blob alone and cut up the wide one
+        for strategy in [
+            EmptyClusterStrategy::FarthestPoint,
+            EmptyClusterStrategy::SplitLargest,
+        ] {
+            let (points, cfg) = stranded_centroid(strategy);
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            assert_eq!(result.counts[0], 100, "{strategy:?}");
+            assert!(
+                result.centroids[2][0] > 40.0,
+                "{strategy:?}: {:?}",
+                result.centroids
+            );
+        }
+    }
+
+    #[test]
+    fn refills_that_never_win_points_do_not_block_convergence() {
+        // Three distinct colors but k = 8: five slots stay empty however
+        // often they are refilled
+        let points: Vec<[f32; 3]> = (0..3000)
+            .map(|i| [(i % 3) as f32 * 30.0, 0.0, 0.0])
+            .collect();
+        for strategy in [
+            EmptyClusterStrategy::Random,
+            EmptyClusterStrategy::FarthestPoint,
+            EmptyClusterStrategy::SplitLargest,
+        ] {
+            let cfg = KMeansConfig {
+                k: 8,
+                max_iters: 100,
+                on_empty: strategy,
+                ..KMeansConfig::default()
+            };
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            assert!(result.converged, "{strategy:?}: {result:?}");
+            assert!(result.iterations < 100, "{strategy:?}");
+            assert_eq!(result.counts.iter().sum::<usize>(), 3000, "{strategy:?}");
+        }
+    }
+
+    #[test]
+    fn drop_returns_fewer_clusters_on_both_assignment_paths() {
+        for assignment in [AssignmentMode::Lloyd, AssignmentMode::Hamerly] {
+            let (points, cfg) = stranded_centroid(EmptyClusterStrategy::Drop);
+            let cfg = KMeansConfig {
+                assignment,
+                trace: true,
+                ..cfg
+            };
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            assert_eq!(result.centroids.len(), 2, "{assignment:?}");
+            assert_eq!(result.counts, vec![100, 100], "{assignment:?}");
+            assert_eq!(
+                result.empty_clusters,
+                Some(EmptyClusterReport {
+                    strategy: EmptyClusterStrategy::Drop,
+                    occurrences: 1,
+                })
+            );
+            let trace = result.trace.expect("trace");
+            assert_eq!(trace[0].empty_clusters, 1);
+            assert!(
+                trace[1..].iter().all(|it| it.reassigned == Some(0)),
+                "{trace:?}"
+            );
+        }
+    }
+}
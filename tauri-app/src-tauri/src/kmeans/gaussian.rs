@@ -0,0 +1,230 @@
+//! Gaussian linear algebra behind the mixture model: weighted 3×3 covariance
+//! estimates, their Cholesky factors, and the log-densities and
+//! responsibilities built on them.
+
+use rayon::prelude::*;
+
+use super::gmm::{CovarianceKind, GmmConfig};
+use super::{Metric, PointsSoa};
+
+/// Points per parallel work unit; fixed so the f64 sums do not depend on the
+/// thread count.
+const CHUNK: usize = 4096;
+
+/// `ln(2π)`.
+const LN_TAU: f64 = 1.837_877_066_409_345_5;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Component {
+    pub(super) mean: [f64; 3],
+    pub(super) cov: [[f64; 3]; 3],
+    pub(super) weight: f64,
+    /// Lower Cholesky factor of `cov`.
+    chol: [[f64; 3]; 3],
+    /// `ln(weight) - ln|2πΣ| / 2`; `-∞` for a component that lost every point.
+    pub(super) log_norm: f64,
+}
+
+impl Component {
+    pub(super) fn new(mean: [f64; 3], cov: [[f64; 3]; 3], weight: f64) -> Self {
+        // Rounding can leave a nearly singular full matrix indefinite; fall
+        // back to its diagonal, which the regularisation keeps positive
+        let (cov, chol) = match cholesky(&cov) {
+            Some(chol) => (cov, chol),
+            None => {
+                let diag = diagonal(&cov);
+                (diag, cholesky(&diag).expect("regularised diagonal"))
+            }
+        };
+        let log_det: f64 = (0..3).map(|i| 2.0 * chol[i][i].ln()).sum();
+        Self {
+            mean,
+            cov,
+            weight,
+            chol,
+            log_norm: weight.ln() - 0.5 * (3.0 * LN_TAU + log_det),
+        }
+    }
+
+    /// The same component with no points left: it keeps its mean and
+    /// covariance and drops out with a zero mixing weight.
+    pub(super) fn emptied(self) -> Self {
+        Self {
+            weight: 0.0,
+            log_norm: f64::NEG_INFINITY,
+            ..self
+        }
+    }
+
+    /// `ln(weight · N(mean + d | mean, cov))`.
+    pub(super) fn log_density(&self, d: [f64; 3]) -> f64 {
+        // Solve L y = d; the Mahalanobis distance is |y|²
+        let l = &self.chol;
+        let y0 = d[0] / l[0][0];
+        let y1 = (d[1] - l[1][0] * y0) / l[1][1];
+        let y2 = (d[2] - l[2][0] * y0 - l[2][1] * y1) / l[2][2];
+        self.log_norm - 0.5 * (y0 * y0 + y1 * y1 + y2 * y2)
+    }
+}
+
+pub(super) fn identity(scale: f64) -> [[f64; 3]; 3] {
+    let mut m = [[0.0; 3]; 3];
+    for (axis, row) in m.iter_mut().enumerate() {
+        row[axis] = scale;
+    }
+    m
+}
+
+pub(super) fn diagonal(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut d = [[0.0; 3]; 3];
+    for axis in 0..3 {
+        d[axis][axis] = m[axis][axis];
+    }
+    d
+}
+
+fn cholesky(m: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let mut l = [[0.0f64; 3]; 3];
+    for i in 0..3 {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|p| l[i][p] * l[j][p]).sum();
+            if i == j {
+                let pivot = m[i][i] - sum;
+                if pivot.is_nan() || pivot <= 0.0 {
+                    return None;
+                }
+                l[i][i] = pivot.sqrt();
+            } else {
+                l[i][j] = (m[i][j] - sum) / l[j][j];
+            }
+        }
+    }
+    Some(l)
+}
+
+/// `point - mean` per axis, the short way round on a circular axis.
+#[inline]
+pub(super) fn deviation(metric: &Metric, mean: &[f64; 3], point: (f32, f32, f32)) -> [f64; 3] {
+    let p = [point.0, point.1, point.2];
+    std::array::from_fn(|axis| metric.axis_delta(axis, mean[axis] as f32, p[axis]) as f64)
+}
+
+/// Recompute responsibilities for the current components; returns the mean
+/// log-likelihood per unit weight.
+pub(super) fn responsibilities(
+    dataset: &PointsSoa,
+    components: &[Component],
+    metric: &Metric,
+    resp: &mut [f32],
+) -> f64 {
+    let k = components.len();
+    let partials: Vec<(f64, f64)> = resp
+        .par_chunks_mut(CHUNK * k)
+        .enumerate()
+        .map(|(chunk, rows)| {
+            let mut log_p = vec![0.0f64; k];
+            let (mut ll, mut total) = (0.0f64, 0.0f64);
+            for (offset, row) in rows.chunks_mut(k).enumerate() {
+                let idx = chunk * CHUNK + offset;
+                let point = dataset.component_tuple(idx);
+                for (lp, c) in log_p.iter_mut().zip(components) {
+                    *lp = c.log_density(deviation(metric, &c.mean, point));
+                }
+                // Log-sum-exp so far-off points do not underflow to 0/0
+                let max = log_p.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let sum: f64 = log_p.iter().map(|lp| (lp - max).exp()).sum();
+                for (r, lp) in row.iter_mut().zip(&log_p) {
+                    *r = ((lp - max).exp() / sum) as f32;
+                }
+                let w = dataset.weight(idx) as f64;
+                ll += w * (max + sum.ln());
+                total += w;
+            }
+            (ll, total)
+        })
+        .collect();
+    let (ll, total) = partials
+        .into_iter()
+        .fold((0.0, 0.0), |(ll, total), (l, t)| (ll + l, total + t));
+    ll / total.max(f64::MIN_POSITIVE)
+}
+
+/// Sum `add(idx, sums)` over every point, chunked and merged in order so the
+/// result does not depend on the thread count.
+pub(super) fn accumulate<const W: usize>(
+    len: usize,
+    k: usize,
+    add: impl Fn(usize, &mut [[f64; W]]) + Sync,
+) -> Vec<[f64; W]> {
+    let chunks: Vec<Vec<[f64; W]>> = (0..len.div_ceil(CHUNK))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut sums = vec![[0.0f64; W]; k];
+            for idx in chunk * CHUNK..((chunk + 1) * CHUNK).min(len) {
+                add(idx, &mut sums);
+            }
+            sums
+        })
+        .collect();
+    let mut totals = vec![[0.0f64; W]; k];
+    for chunk in chunks {
+        for (total, part) in totals.iter_mut().zip(chunk) {
+            for (t, p) in total.iter_mut().zip(part) {
+                *t += p;
+            }
+        }
+    }
+    totals
+}
+
+/// Regularised covariance of every component with a mean, from the
+/// responsibility-weighted scatter of the points around it; `nk` is each
+/// component's total responsibility.
+pub(super) fn covariances(
+    dataset: &PointsSoa,
+    resp: &[f32],
+    means: &[Option<[f64; 3]>],
+    nk: &[f64],
+    metric: &Metric,
+    cfg: &GmmConfig,
+) -> Vec<Option<[[f64; 3]; 3]>> {
+    let k = means.len();
+    // Upper triangle: [xx, xy, xz, yy, yz, zz]
+    let scatter = accumulate::<6>(dataset.len(), k, |idx, sums| {
+        let point = dataset.component_tuple(idx);
+        let w = dataset.weight(idx) as f64;
+        for (comp, sum) in sums.iter_mut().enumerate() {
+            let Some(mean) = &means[comp] else { continue };
+            let rw = resp[idx * k + comp] as f64 * w;
+            if rw == 0.0 {
+                continue;
+            }
+            let [dx, dy, dz] = deviation(metric, mean, point);
+            sum[0] += rw * dx * dx;
+            sum[1] += rw * dx * dy;
+            sum[2] += rw * dx * dz;
+            sum[3] += rw * dy * dy;
+            sum[4] += rw * dy * dz;
+            sum[5] += rw * dz * dz;
+        }
+    });
+
+    let reg = cfg.reg_covar as f64;
+    scatter
+        .iter()
+        .zip(means)
+        .zip(nk)
+        .map(|((sums, mean), &nk)| {
+            mean.as_ref()?;
+            let s = sums.map(|v| v / nk);
+            let mut cov = [[s[0], s[1], s[2]], [s[1], s[3], s[4]], [s[2], s[4], s[5]]];
+            if cfg.covariance == CovarianceKind::Diagonal {
+                cov = diagonal(&cov);
+            }
+            for (axis, row) in cov.iter_mut().enumerate() {
+                row[axis] += reg;
+            }
+            Some(cov)
+        })
+        .collect()
+}
@@ -0,0 +1,342 @@
+//! Gaussian mixture clustering by expectation–maximisation, seeded from a
+//! k-means run. Each component keeps its own covariance, so a broad gradient
+//! and a small saturated accent can sit side by side without the gradient
+//! being chopped into k-means' equal-variance pieces.
+
+use serde::{Deserialize, Serialize};
+
+use super::gaussian::{accumulate, covariances, identity, responsibilities, Component};
+use super::{run_kmeans_soa, KMeansConfig, KMeansError, Metric, PointsSoa, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CovarianceKind {
+    /// One variance per axis: axis-aligned ellipsoids.
+    #[default]
+    Diagonal,
+    /// Full 3×3 covariance, for clusters stretched across axes (e.g. a
+    /// gradient that darkens and desaturates at the same time).
+    Full,
+}
+
+#[derive(Debug, Clone)]
+pub struct GmmConfig {
+    /// Seeding run. Sets k, the seed and the distance mode; a circular hue
+    /// axis is wrapped in EM too. Axis weights only shape the seeding, and
+    /// pinned centroids are free to move once EM starts.
+    pub kmeans: KMeansConfig,
+    pub covariance: CovarianceKind,
+    /// EM iterations after seeding.
+    pub max_iters: usize,
+    /// Stop once the mean log-likelihood per unit weight changes by less than this.
+    pub tol: f32,
+    /// Added to every variance so a component sitting on one repeated color
+    /// stays invertible.
+    pub reg_covar: f32,
+    /// Fill [`GmmResult::memberships`].
+    pub return_memberships: bool,
+}
+
+impl Default for GmmConfig {
+    fn default() -> Self {
+        Self {
+            kmeans: KMeansConfig::default(),
+            covariance: CovarianceKind::Diagonal,
+            max_iters: 100,
+            tol: 1e-4,
+            reg_covar: 1e-2,
+            return_memberships: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GmmResult {
+    pub means: Vec<[f32; 3]>,
+    /// One symmetric matrix per component; off-diagonal entries are zero for
+    /// [`CovarianceKind::Diagonal`].
+    pub covariances: Vec<[[f32; 3]; 3]>,
+    /// Mixing weights; they sum to 1.
+    pub weights: Vec<f32>,
+    /// Weight of the points most likely to belong to each component, rounded
+    /// like [`super::KMeansResult::counts`].
+    pub counts: Vec<usize>,
+    /// Responsibilities, `k` per point in input order, when
+    /// [`GmmConfig::return_memberships`] is set. Every row sums to 1.
+    pub memberships: Option<Vec<f32>>,
+    /// Mean log-likelihood per unit weight under the returned parameters.
+    pub log_likelihood: f32,
+    /// EM iterations run after the k-means seeding.
+    pub iterations: usize,
+    /// Whether the log-likelihood settled within `tol` before `max_iters`.
+    pub converged: bool,
+}
+
+impl GmmResult {
+    /// Per component, the RMS standard deviation `sqrt(trace(Σ) / 3)` in
+    /// clustering-space units: one number for how spread a palette color is.
+    pub fn spreads(&self) -> Vec<f32> {
+        self.covariances
+            .iter()
+            .map(|cov| ((cov[0][0] + cov[1][1] + cov[2][2]) / 3.0).sqrt())
+            .collect()
+    }
+
+    /// Responsibilities of point `idx`, when memberships were requested.
+    pub fn membership(&self, idx: usize) -> Option<&[f32]> {
+        let k = self.means.len();
+        self.memberships
+            .as_deref()
+            .map(|rows| &rows[idx * k..(idx + 1) * k])
+    }
+}
+
+pub fn run_gmm(points: &[[f32; 3]], cfg: &GmmConfig) -> Result<GmmResult> {
+    let dataset = PointsSoa::from_points(points);
+    run_gmm_soa(&dataset, cfg)
+}
+
+/// GMM over deduplicated points where `weights[i]` is how many times
+/// `points[i]` occurs.
+pub fn run_gmm_weighted(
+    points: &[[f32; 3]],
+    weights: &[f32],
+    cfg: &GmmConfig,
+) -> Result<GmmResult> {
+    let dataset = PointsSoa::from_weighted_points(points, weights)?;
+    run_gmm_soa(&dataset, cfg)
+}
+
+pub fn run_gmm_soa(dataset: &PointsSoa, cfg: &GmmConfig) -> Result<GmmResult> {
+    if !(cfg.reg_covar > 0.0 && cfg.reg_covar.is_finite()) {
+        return Err(KMeansError::InvalidConfig(
+            "reg_covar must be finite and > 0",
+        ));
+    }
+    let seed_cfg = KMeansConfig {
+        return_labels: true,
+        ..cfg.kmeans.clone()
+    };
+    let seed = run_kmeans_soa(dataset, &seed_cfg)?;
+    let metric = Metric::new(&cfg.kmeans)?;
+    let k = seed.centroids.len();
+
+    // Hard k-means labels are the first responsibilities
+    let mut resp = vec![0.0f32; dataset.len() * k];
+    let labels = seed.labels.expect("return_labels was set");
+    for (row, &label) in resp.chunks_mut(k).zip(&labels) {
+        row[label as usize] = 1.0;
+    }
+    let mut components: Vec<Component> = seed
+        .centroids
+        .iter()
+        .map(|c| Component::new(c.map(f64::from), identity(1.0), 1.0 / k as f64))
+        .collect();
+    m_step(dataset, &resp, &mut components, cfg, &metric);
+
+    let mut previous: Option<f64> = None;
+    let mut iterations = 0;
+    let mut converged = false;
+    let log_likelihood = loop {
+        let ll = responsibilities(dataset, &components, &metric, &mut resp);
+        if previous.is_some_and(|prev| (ll - prev).abs() < cfg.tol as f64) {
+            converged = true;
+            break ll;
+        }
+        if iterations == cfg.max_iters {
+            break ll;
+        }
+        m_step(dataset, &resp, &mut components, cfg, &metric);
+        iterations += 1;
+        previous = Some(ll);
+    };
+
+    let mut hard = vec![0.0f64; k];
+    for (idx, row) in resp.chunks(k).enumerate() {
+        let best = (0..k)
+            .max_by(|&a, &b| row[a].total_cmp(&row[b]).then(b.cmp(&a)))
+            .expect("k > 0");
+        hard[best] += dataset.weight(idx) as f64;
+    }
+
+    Ok(GmmResult {
+        means: components
+            .iter()
+            .map(|c| c.mean.map(|v| v as f32))
+            .collect(),
+        covariances: components
+            .iter()
+            .map(|c| c.cov.map(|row| row.map(|v| v as f32)))
+            .collect(),
+        weights: components.iter().map(|c| c.weight as f32).collect(),
+        counts: hard.iter().map(|w| w.round() as usize).collect(),
+        memberships: cfg.return_memberships.then_some(resp),
+        log_likelihood: log_likelihood as f32,
+        iterations,
+        converged,
+    })
+}
+
+/// Refit every component to the responsibilities. Components with no weight
+/// keep their mean and covariance and drop out with a zero mixing weight.
+fn m_step(
+    dataset: &PointsSoa,
+    resp: &[f32],
+    components: &mut [Component],
+    cfg: &GmmConfig,
+    metric: &Metric,
+) {
+    let k = components.len();
+    let responsibility = |idx: usize, comp: usize| resp[idx * k + comp] as f64;
+
+    // [weight, Σx, Σy, Σz, Σsin, Σcos]
+    let moments = accumulate::<6>(dataset.len(), k, |idx, sums| {
+        let (px, py, pz) = dataset.component_tuple(idx);
+        let w = dataset.weight(idx) as f64;
+        let angle = metric
+            .circular_axis
+            .map(|axis| ([px, py, pz][axis] * metric.angle_scale).sin_cos());
+        for (comp, sum) in sums.iter_mut().enumerate() {
+            let rw = responsibility(idx, comp) * w;
+            if rw == 0.0 {
+                continue;
+            }
+            sum[0] += rw;
+            sum[1] += rw * px as f64;
+            sum[2] += rw * py as f64;
+            sum[3] += rw * pz as f64;
+            if let Some((sin, cos)) = angle {
+                sum[4] += rw * sin as f64;
+                sum[5] += rw * cos as f64;
+            }
+        }
+    });
+    let total: f64 = moments.iter().map(|m| m[0]).sum();
+    let means: Vec<Option<[f64; 3]>> = moments
+        .iter()
+        .map(|m| {
+            if m[0] <= 0.0 {
+                return None;
+            }
+            let mut mean = [m[1] / m[0], m[2] / m[0], m[3] / m[0]];
+            if let Some(axis) = metric.circular_axis {
+                mean[axis] = metric.circular_mean(m[4] as f32, m[5] as f32) as f64;
+            }
+            Some(mean)
+        })
+        .collect();
+
+    let nk: Vec<f64> = moments.iter().map(|m| m[0]).collect();
+    let covs = covariances(dataset, resp, &means, &nk, metric, cfg);
+    for ((component, mean), (cov, &nk)) in
+        components.iter_mut().zip(&means).zip(covs.iter().zip(&nk))
+    {
+        *component = match (mean, cov) {
+            (Some(mean), Some(cov)) => Component::new(*mean, *cov, nk / total),
+            _ => component.emptied(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A wide 2000-point gradient along x and a tight 100-point accent.
+    fn gradient_and_accent() -> Vec<[f32; 3]> {
+        let mut points = Vec::new();
+        for i in 0..2000 {
+            let x = (i % 200) as f32 * 0.3 - 30.0;
+            let y = (i / 200) as f32 * 0.4 - 1.8;
+            points.push([50.0 + x, y, 0.0]);
+        }
+        for i in 0..100 {
+            let t = (i % 5) as f32 * 0.2 - 0.4;
+            points.push([50.0 + t, 60.0 - t, 10.0]);
+        }
+        points
+    }
+
+    #[test]
+    fn components_keep_their_own_spread_and_weight() {
+        let points = gradient_and_accent();
+        let cfg = GmmConfig {
+            kmeans: KMeansConfig {
+                k: 2,
+                seed: 4,
+                ..KMeansConfig::default()
+            },
+            return_memberships: true,
+            ..GmmConfig::default()
+        };
+        let result = run_gmm(&points, &cfg).expect("gmm");
+        assert!(result.converged, "{} iterations", result.iterations);
+
+        let accent = result
+            .means
+            .iter()
+            .position(|m| m[1] > 30.0)
+            .expect("accent component");
+        let gradient = 1 - accent;
+        assert!((result.means[accent][1] - 60.0).abs() < 0.5, "{result:?}");
+        assert!((result.means[gradient][0] - 50.0).abs() < 0.5, "{result:?}");
+        assert!((result.weights[accent] - 100.0 / 2100.0).abs() < 1e-3);
+        assert!((result.weights.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert_eq!(result.counts[accent], 100);
+        assert_eq!(result.counts[gradient], 2000);
+
+        let spreads = result.spreads();
+        assert!(spreads[gradient] > 20.0 * spreads[accent], "{spreads:?}");
+        // The gradient runs along x only
+        let cov = result.covariances[gradient];
+        assert!(cov[0][0] > 100.0 * cov[2][2], "{cov:?}");
+
+        for idx in [0, 1999, 2050] {
+            let row = result.membership(idx).expect("memberships");
+            assert!((row.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        }
+        assert!(result.membership(2050).unwrap()[accent] > 0.99);
+    }
+
+    #[test]
+    fn full_covariance_follows_a_diagonal_cluster() {
+        // A thin streak where x and y rise together
+        let mut points = Vec::new();
+        for i in 0..600 {
+            let t = (i % 60) as f32 - 30.0;
+            let jitter = (i / 60) as f32 * 0.1 - 0.45;
+            points.push([t + jitter, t - jitter, 0.0]);
+        }
+        let base = GmmConfig {
+            kmeans: KMeansConfig {
+                k: 1,
+                ..KMeansConfig::default()
+            },
+            ..GmmConfig::default()
+        };
+        let diagonal = run_gmm(&points, &base).expect("gmm");
+        let full = run_gmm(
+            &points,
+            &GmmConfig {
+                covariance: CovarianceKind::Full,
+                ..base.clone()
+            },
+        )
+        .expect("gmm");
+        assert_eq!(diagonal.covariances[0][0][1], 0.0);
+        let cov = full.covariances[0];
+        assert_eq!(cov[0][1], cov[1][0]);
+        let correlation = cov[0][1] / (cov[0][0] * cov[1][1]).sqrt();
+        assert!(correlation > 0.99, "{cov:?}");
+        assert!(full.log_likelihood > diagonal.log_likelihood + 1.0);
+
+        let no_reg = GmmConfig {
+            reg_covar: 0.0,
+            ..base
+        };
+        assert!(matches!(
+            run_gmm(&points, &no_reg),
+            Err(KMeansError::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+//! Hamerly's accelerated assignment (G. Hamerly, "Making k-means even faster", SDM 2010).
+//!
+//! Every point remembers its centroid and a lower bound on the distance to any
+//! other centroid. A point is only re-scanned when the exact distance to its own
+//! centroid is not provably below both that bound and half the gap between its
+//! centroid and the nearest other one. A skipped point therefore keeps exactly
+//! the centroid a full scan would pick, and sums are accumulated in the same
+//! chunk order as [`super::assignment_step`], so centroids and counts match the
+//! brute-force path.
+
+use rayon::prelude::*;
+
+use super::{
+    assignment_chunk_size, best_two_centroids, merge_partials, CentroidsSoa, ClusterPartial,
+    Metric, PointsSoa,
+};
+
+/// Relative slack on the skip test so f32 rounding in the bounds never skips a
+/// point whose nearest centroid actually changed.
+const BOUND_SLACK: f32 = 1e-4;
+
+pub(super) struct Hamerly {
+    assigned: Vec<u32>,
+    /// Lower bound on the (unsquared) distance to the second-nearest centroid.
+    lower: Vec<f32>,
+    /// Half the distance from each centroid to its nearest other centroid.
+    half_gap: Vec<f32>,
+    initialized: bool,
+}
+
+impl Hamerly {
+    pub(super) fn new(points: usize) -> Self {
+        Self {
+            assigned: vec![0; points],
+            lower: vec![0.0; points],
+            half_gap: Vec::new(),
+            initialized: false,
+        }
+    }
+
+    pub(super) fn assignment_step(
+        &mut self,
+        points: &PointsSoa,
+        centroids: &CentroidsSoa,
+        metric: &Metric,
+    ) -> (Vec<ClusterPartial>, f32, usize) {
+        debug_assert_eq!(points.len(), self.assigned.len());
+        let k = centroids.len();
+        self.update_half_gaps(centroids, metric);
+
+        let chunk_size = assignment_chunk_size(k);
+        let initialized = self.initialized;
+        let half_gap = &self.half_gap;
+        let (chunk_partials, reassigned): (Vec<(Vec<ClusterPartial>, f32)>, Vec<usize>) = self
+            .assigned
+            .par_chunks_mut(chunk_size)
+            .zip(self.lower.par_chunks_mut(chunk_size))
+            .enumerate()
+            .map(|(chunk_idx, (assigned, lower))| {
+                let start = chunk_idx * chunk_size;
+                let mut partials = vec![ClusterPartial::default(); k];
+                let mut inertia = 0.0f32;
+                let mut reassigned = 0;
+                for (offset, (label, bound)) in
+                    assigned.iter_mut().zip(lower.iter_mut()).enumerate()
+                {
+                    let idx = start + offset;
+                    let (px, py, pz) = points.component_tuple(idx);
+                    let w = points.weight(idx);
+
+                    let mut dist = None;
+                    if initialized {
+                        let own = *label as usize;
+                        let d = metric.point_to_centroid(points, idx, centroids, own);
+                        if d.sqrt() * (1.0 + BOUND_SLACK) < bound.max(half_gap[own]) {
+                            dist = Some(d);
+                        }
+                    }
+                    let dist = dist.unwrap_or_else(|| {
+                        let (best, best_dist, second_dist) =
+                            best_two_centroids(points, idx, centroids, metric);
+                        if !initialized || *label != best as u32 {
+                            reassigned += 1;
+                        }
+                        *label = best as u32;
+                        *bound = second_dist.sqrt();
+                        best_dist
+                    });
+
+                    let pos = points.position(idx);
+                    partials[*label as usize].add(px, py, pz, pos, w, metric);
+                    inertia += dist * w;
+                }
+                ((partials, inertia), reassigned)
+            })
+            .unzip();
+
+        self.initialized = true;
+        let (partials, inertia) = merge_partials(chunk_partials, k);
+        (partials, inertia, reassigned.iter().sum())
+    }
+
+    /// Loosen each lower bound by the largest move among the centroids the
+    /// point is not assigned to.
+    pub(super) fn centroids_moved(
+        &mut self,
+        before: &CentroidsSoa,
+        after: &CentroidsSoa,
+        metric: &Metric,
+    ) {
+        let mut first = (usize::MAX, 0.0f32);
+        let mut second = 0.0f32;
+        for idx in 0..after.len() {
+            let moved = metric.between(before, idx, after, idx).sqrt();
+            if moved > first.1 {
+                second = first.1;
+                first = (idx, moved);
+            } else if moved > second {
+                second = moved;
+            }
+        }
+
+        self.lower
+            .par_iter_mut()
+            .zip(self.assigned.par_iter())
+            .for_each(|(bound, &label)| {
+                let others = if label as usize == first.0 {
+                    second
+                } else {
+                    first.1
+                };
+                *bound -= others;
+            });
+    }
+
+    /// Renumber assignments after the (empty) clusters at `removed`, in
+    /// ascending order, were deleted. Lower bounds stay valid: removing
+    /// centroids can only push the second-nearest one further away.
+    pub(super) fn remove_centroids(&mut self, removed: &[usize]) {
+        super::relabel_after_removal(&mut self.assigned, removed);
+    }
+
+    fn update_half_gaps(&mut self, centroids: &CentroidsSoa, metric: &Metric) {
+        let k = centroids.len();
+        self.half_gap.clear();
+        self.half_gap.resize(k, f32::INFINITY);
+        for a in 0..k {
+            for b in (a + 1)..k {
+                let half = 0.5 * metric.between(centroids, a, centroids, b).sqrt();
+                self.half_gap[a] = self.half_gap[a].min(half);
+                self.half_gap[b] = self.half_gap[b].min(half);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    fn blobs(count: usize, centers: usize, seed: u64) -> Vec<[f32; 3]> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let means: Vec<[f32; 3]> = (0..centers)
+            .map(|_| {
+                [
+                    rng.gen_range(0.0..100.0),
+                    rng.gen_range(-50.0..50.0),
+                    rng.gen_range(-50.0..50.0),
+                ]
+            })
+            .collect();
+        (0..count)
+            .map(|_| {
+                let m = means[rng.gen_range(0..centers)];
+                [
+                    m[0] + rng.gen_range(-4.0..4.0),
+                    m[1] + rng.gen_range(-4.0..4.0),
+                    m[2] + rng.gen_range(-4.0..4.0),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hamerly_matches_lloyd_exactly() {
+        let points = blobs(6000, 24, 9);
+        let lloyd = KMeansConfig {
+            k: 40,
+            max_iters: 60,
+            tol: 1e-5,
+            seed: 4,
+            return_labels: true,
+            ..KMeansConfig::default()
+        };
+        let hamerly = KMeansConfig {
+            assignment: AssignmentMode::Hamerly,
+            ..lloyd.clone()
+        };
+        let a = run_kmeans(&points, &lloyd).expect("lloyd");
+        let b = run_kmeans(&points, &hamerly).expect("hamerly");
+        assert_eq!(a.iterations, b.iterations);
+        assert_eq!(a.counts, b.counts);
+        assert_eq!(a.centroids, b.centroids);
+        assert_eq!(a.labels, b.labels);
+        assert!((a.inertia - b.inertia).abs() <= 1e-3 * a.inertia);
+    }
+
+    #[test]
+    fn hamerly_matches_lloyd_with_circular_weighted_metric() {
+        let points: Vec<[f32; 3]> = blobs(3000, 12, 21)
+            .into_iter()
+            .map(|[l, a, h]| [l, a, (h * 7.0).rem_euclid(360.0)])
+            .collect();
+        let lloyd = KMeansConfig {
+            k: 16,
+            max_iters: 40,
+            tol: 1e-5,
+            seed: 8,
+            distance: DistanceMode::Circular {
+                axis: 2,
+                period: 360.0,
+            },
+            axis_weights: Some([1.0, 0.5, 2.0]),
+            ..KMeansConfig::default()
+        };
+        let hamerly = KMeansConfig {
+            assignment: AssignmentMode::Hamerly,
+            ..lloyd.clone()
+        };
+        let a = run_kmeans(&points, &lloyd).expect("lloyd");
+        let b = run_kmeans(&points, &hamerly).expect("hamerly");
+        assert_eq!(a.counts, b.counts);
+        for (x, y) in a.centroids.iter().zip(b.centroids.iter()) {
+            for j in 0..3 {
+                assert!((x[j] - y[j]).abs() < 1e-3, "{x:?} vs {y:?}");
+            }
+        }
+    }
+}
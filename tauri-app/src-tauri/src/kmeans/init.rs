@@ -0,0 +1,335 @@
+//! Initial centroids for a k-means run.
+
+use rand::{rngs::SmallRng, seq::index, Rng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    best_centroid, farthest_points, kmeans_plus_plus, pick_proportional, CentroidsSoa, Metric,
+    PointsSoa, Result,
+};
+use crate::quantize::{MedianCut, Quantizer, Wu};
+
+/// Datasets with at least this many points seed with k-means|| under
+/// [`KMeansInit::Auto`]; smaller ones use k-means++.
+pub const PARALLEL_INIT_MIN_POINTS: usize = 50_000;
+
+/// k-means|| sampling rounds and per-round oversampling factor (× k), as in
+/// Spark MLlib's defaults.
+const PARALLEL_ROUNDS: usize = 2;
+const PARALLEL_OVERSAMPLING: f64 = 2.0;
+
+/// How a run picks its starting centroids when there is no warm start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KMeansInit {
+    /// k-means|| from [`PARALLEL_INIT_MIN_POINTS`] points up, k-means++ below.
+    /// Results report the init it resolved to.
+    #[default]
+    Auto,
+    /// D²-weighted sampling; O(n·k) and sequential.
+    #[serde(rename = "kmeans++", alias = "kmeansPlusPlus")]
+    KMeansPlusPlus,
+    /// Scalable k-means++ (Bahmani et al.): a few parallel rounds oversample
+    /// candidates by D², then weighted k-means++ picks k of them. Results do
+    /// not depend on the number of threads.
+    #[serde(rename = "kmeans||", alias = "kmeansParallel")]
+    KMeansParallel,
+    /// k distinct points drawn uniformly (weights are ignored).
+    Random,
+    /// Centroids of a median-cut pass; deterministic.
+    #[serde(alias = "median_cut")]
+    MedianCut,
+    /// Centroids of a Wu quantizer pass; deterministic.
+    Wu,
+}
+
+impl KMeansInit {
+    /// Whether every run with this init starts from the same centroids, so
+    /// restarts gain nothing from repeating it.
+    pub fn is_deterministic(self) -> bool {
+        matches!(self, KMeansInit::MedianCut | KMeansInit::Wu)
+    }
+
+    /// The concrete init `Auto` stands for on a dataset of `points` points.
+    pub fn resolve(self, points: usize) -> KMeansInit {
+        match self {
+            KMeansInit::Auto if points >= PARALLEL_INIT_MIN_POINTS => KMeansInit::KMeansParallel,
+            KMeansInit::Auto => KMeansInit::KMeansPlusPlus,
+            other => other,
+        }
+    }
+}
+
+/// `k` starting centroids: `pinned` first, then `k - pinned.len()` seeded by `init`.
+pub(super) fn initial_centroids(
+    dataset: &PointsSoa,
+    pinned: &[[f32; 3]],
+    k: usize,
+    init: KMeansInit,
+    metric: &Metric,
+    rng: &mut SmallRng,
+) -> Result<CentroidsSoa> {
+    let centroids = match init.resolve(dataset.len()) {
+        KMeansInit::Auto => unreachable!("resolve never returns Auto"),
+        KMeansInit::KMeansPlusPlus => kmeans_plus_plus(dataset, pinned, k, metric, rng),
+        KMeansInit::KMeansParallel => kmeans_parallel(dataset, pinned, k, metric, rng),
+        KMeansInit::Random => {
+            let mut centroids = CentroidsSoa::from_vec(pinned);
+            for idx in index::sample(rng, dataset.len(), k - pinned.len()) {
+                let (x, y, z) = dataset.component_tuple(idx);
+                centroids.cx.push(x);
+                centroids.cy.push(y);
+                centroids.cz.push(z);
+            }
+            centroids
+        }
+        KMeansInit::MedianCut => from_quantizer(&MedianCut, dataset, pinned, k, metric)?,
+        KMeansInit::Wu => from_quantizer(&Wu, dataset, pinned, k, metric)?,
+    };
+    Ok(centroids)
+}
+
+fn kmeans_parallel(
+    dataset: &PointsSoa,
+    pinned: &[[f32; 3]],
+    k: usize,
+    metric: &Metric,
+    rng: &mut SmallRng,
+) -> CentroidsSoa {
+    let n = dataset.len();
+    let first = match dataset.weights() {
+        Some(weights) => {
+            let total: f32 = weights.iter().sum();
+            if total > 0.0 {
+                pick_proportional(weights.iter().copied(), rng.gen::<f32>() * total)
+            } else {
+                rng.gen_range(0..n)
+            }
+        }
+        None => rng.gen_range(0..n),
+    };
+    let (fx, fy, fz) = dataset.component_tuple(first);
+    let mut candidates = vec![[fx, fy, fz]];
+    // Weighted squared distance to, and index of, each point's nearest candidate
+    let mut nearest: Vec<(f32, u32)> = (0..n)
+        .into_par_iter()
+        .map(|idx| {
+            let (px, py, pz) = dataset.component_tuple(idx);
+            (
+                metric.distance(px, py, pz, fx, fy, fz) * dataset.weight(idx),
+                0,
+            )
+        })
+        .collect();
+
+    // Per-point coin flips come from a hash of (salt, round, index) rather
+    // than a shared RNG so the sample is the same on any number of threads
+    let salt: u64 = rng.gen();
+    let oversampling = PARALLEL_OVERSAMPLING * k as f64;
+    for round in 0..PARALLEL_ROUNDS {
+        let cost = ordered_sum(&nearest);
+        if cost <= 0.0 {
+            break;
+        }
+        let picked: Vec<usize> = (0..n)
+            .into_par_iter()
+            .filter(|&idx| {
+                let p = oversampling * nearest[idx].0 as f64 / cost;
+                unit_hash(salt, round as u64, idx as u64) < p
+            })
+            .collect();
+        if picked.is_empty() {
+            continue;
+        }
+
+        let offset = candidates.len();
+        candidates.extend(picked.iter().map(|&idx| {
+            let (px, py, pz) = dataset.component_tuple(idx);
+            [px, py, pz]
+        }));
+        let batch = CentroidsSoa::from_vec(&candidates[offset..]);
+        nearest.par_iter_mut().enumerate().for_each(|(idx, slot)| {
+            let (px, py, pz) = dataset.component_tuple(idx);
+            let (local, dist) = best_centroid(px, py, pz, &batch, metric);
+            let dist = dist * dataset.weight(idx);
+            if dist < slot.0 {
+                *slot = (dist, (offset + local) as u32);
+            }
+        });
+    }
+
+    if candidates.len() <= k {
+        // Too few distinct points were drawn to choose from
+        return kmeans_plus_plus(dataset, pinned, k, metric, rng);
+    }
+    let mut candidate_weights = vec![0.0f64; candidates.len()];
+    for (idx, &(_, label)) in nearest.iter().enumerate() {
+        candidate_weights[label as usize] += dataset.weight(idx) as f64;
+    }
+    let candidate_weights: Vec<f32> = candidate_weights.iter().map(|&w| w as f32).collect();
+    let reduced = PointsSoa::from_weighted_points(&candidates, &candidate_weights)
+        .expect("one weight per candidate");
+    kmeans_plus_plus(&reduced, pinned, k, metric, rng)
+}
+
+/// Sum of the distances in fixed-size chunks, combined in order, so the total
+/// does not depend on how rayon splits the work.
+fn ordered_sum(nearest: &[(f32, u32)]) -> f64 {
+    nearest
+        .par_chunks(4096)
+        .map(|chunk| chunk.iter().map(|&(d, _)| d as f64).sum::<f64>())
+        .collect::<Vec<f64>>()
+        .iter()
+        .sum()
+}
+
+/// Uniform value in `[0, 1)` from a SplitMix64 hash of the inputs.
+fn unit_hash(salt: u64, round: u64, idx: u64) -> f64 {
+    let mut z =
+        salt ^ round.wrapping_mul(0xD1B5_4A32_D192_ED03) ^ idx.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// `pinned` plus quantizer centroids for the free slots, topped up with the
+/// worst-fit points when the quantizer found fewer groups than asked for
+/// (e.g. very few distinct colors).
+fn from_quantizer(
+    quantizer: &dyn Quantizer,
+    dataset: &PointsSoa,
+    pinned: &[[f32; 3]],
+    k: usize,
+    metric: &Metric,
+) -> Result<CentroidsSoa> {
+    let mut seeds = pinned.to_vec();
+    if k > pinned.len() {
+        let points = dataset.to_vec();
+        let fitted = quantizer.quantize(&points, dataset.weights(), k - pinned.len())?;
+        seeds.extend(fitted.centroids);
+    }
+    if seeds.len() == k {
+        return Ok(CentroidsSoa::from_vec(&seeds));
+    }
+
+    let existing = CentroidsSoa::from_vec(&seeds);
+    for idx in farthest_points(dataset, &existing, k - seeds.len(), metric) {
+        let (x, y, z) = dataset.component_tuple(idx);
+        seeds.push([x, y, z]);
+    }
+    Ok(CentroidsSoa::from_vec(&seeds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmeans::{run_kmeans, KMeansConfig, KMeansError};
+    use rand::SeedableRng;
+
+    fn three_blobs() -> Vec<[f32; 3]> {
+        (0..300)
+            .map(|i| {
+                let t = (i % 10) as f32 * 0.1;
+                match i % 3 {
+                    0 => [t, 0.0, 0.0],
+                    1 => [50.0, 50.0 + t, 0.0],
+                    _ => [0.0, 50.0, 50.0 + t],
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_init_is_reported_and_converges() {
+        let points = three_blobs();
+        for init in [
+            KMeansInit::KMeansPlusPlus,
+            KMeansInit::KMeansParallel,
+            KMeansInit::Random,
+            KMeansInit::MedianCut,
+            KMeansInit::Wu,
+        ] {
+            let cfg = KMeansConfig {
+                k: 3,
+                init,
+                ..KMeansConfig::default()
+            };
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            assert_eq!(result.init, Some(init));
+            assert_eq!(result.counts, vec![100, 100, 100], "{init:?}");
+        }
+
+        let warm = KMeansConfig {
+            k: 3,
+            warm_start: Some(vec![[0.0; 3], [50.0, 50.0, 0.0], [0.0, 50.0, 50.0]]),
+            init: KMeansInit::Wu,
+            ..KMeansConfig::default()
+        };
+        assert_eq!(run_kmeans(&points, &warm).expect("kmeans").init, None);
+    }
+
+    #[test]
+    fn kmeans_parallel_ignores_thread_count() {
+        let points: Vec<[f32; 3]> = (0..20_000)
+            .map(|i| {
+                let t = i as f32;
+                [(t * 0.37) % 100.0, (t * 0.71) % 100.0, (t * 0.13) % 100.0]
+            })
+            .collect();
+        let dataset = PointsSoa::from_points(&points);
+        let metric = Metric::new(&KMeansConfig::default()).expect("metric");
+        let seeds_on = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("thread pool");
+            pool.install(|| {
+                let mut rng = SmallRng::seed_from_u64(7);
+                kmeans_parallel(&dataset, &[], 32, &metric, &mut rng).to_vec()
+            })
+        };
+        let single = seeds_on(1);
+        assert_eq!(single.len(), 32);
+        assert_eq!(single, seeds_on(4));
+    }
+
+    #[test]
+    fn auto_switches_to_kmeans_parallel_on_large_data() {
+        assert_eq!(
+            KMeansInit::Auto.resolve(PARALLEL_INIT_MIN_POINTS - 1),
+            KMeansInit::KMeansPlusPlus
+        );
+        assert_eq!(
+            KMeansInit::Auto.resolve(PARALLEL_INIT_MIN_POINTS),
+            KMeansInit::KMeansParallel
+        );
+        assert_eq!(KMeansInit::Wu.resolve(usize::MAX), KMeansInit::Wu);
+        let init: KMeansInit = serde_json::from_str("\"kmeans||\"").unwrap();
+        assert_eq!(init, KMeansInit::KMeansParallel);
+    }
+
+    #[test]
+    fn quantizer_seeds_are_topped_up_to_k() {
+        // The first two points share a Wu grid cell, so Wu alone finds two groups
+        let points = [[0.0, 0.0, 0.0], [0.001, 0.0, 0.0], [100.0, 0.0, 0.0]];
+        let dataset = PointsSoa::from_points(&points);
+        let metric = Metric::new(&KMeansConfig::default()).expect("metric");
+        let mut rng = SmallRng::seed_from_u64(1);
+        let mut seeds = initial_centroids(&dataset, &[], 3, KMeansInit::Wu, &metric, &mut rng)
+            .expect("seeds")
+            .to_vec();
+        assert_eq!(seeds.len(), 3);
+        seeds.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        seeds.dedup();
+        assert_eq!(seeds.len(), 3, "{seeds:?}");
+
+        // Input the quantizer rejects is an error, not a panic
+        let bad = PointsSoa::from_points(&[[0.0, 0.0, 0.0], [f32::NAN, 0.0, 0.0]]);
+        for init in [KMeansInit::Wu, KMeansInit::MedianCut] {
+            let err = initial_centroids(&bad, &[], 2, init, &metric, &mut rng).map(|_| ());
+            assert_eq!(err, Err(KMeansError::NonFinite { index: 1 }), "{init:?}");
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Sculley's web-scale mini-batch k-means: each batch point pulls its nearest
+//! centroid towards itself with a learning rate of `w / (weight seen so far)`,
+//! so centroids settle as they accumulate evidence instead of jumping to each
+//! batch's mean.
+
+use rand::rngs::SmallRng;
+use rayon::prelude::*;
+
+use super::{
+    best_centroid, sample_batch, CentroidsSoa, IterationStats, KMeansConfig, Metric, PointsSoa,
+};
+
+/// Iterations without a new best smoothed inertia before the run stops.
+const PATIENCE: usize = 10;
+
+/// Refine `centroids` in place; returns the number of batches used and
+/// whether the run stopped before `max_iters`.
+pub(super) fn fit(
+    dataset: &PointsSoa,
+    cfg: &KMeansConfig,
+    metric: &Metric,
+    centroids: &mut CentroidsSoa,
+    batch_size: usize,
+    rng: &mut SmallRng,
+    mut trace: Option<&mut Vec<IterationStats>>,
+) -> (usize, bool) {
+    let mut seen = vec![0.0f64; cfg.k];
+    // Weight of the newest batch in the moving average of per-point inertia
+    let alpha = (2.0 * batch_size as f64 / (dataset.len() + 1) as f64).min(1.0);
+    let mut smoothed: Option<f64> = None;
+    let mut best = f64::INFINITY;
+    let mut stale = 0;
+
+    let mut iterations = 0;
+    while iterations < cfg.max_iters {
+        let batch = sample_batch(dataset, batch_size, rng);
+        let nearest: Vec<(usize, f32)> = (0..batch.len())
+            .into_par_iter()
+            .map(|idx| {
+                let (px, py, pz) = batch.component_tuple(idx);
+                best_centroid(px, py, pz, centroids, metric)
+            })
+            .collect();
+
+        let before = centroids.clone();
+        let (mut batch_inertia, mut batch_weight) = (0.0f64, 0.0f64);
+        for (idx, &(label, dist)) in nearest.iter().enumerate() {
+            let w = batch.weight(idx);
+            if w <= 0.0 {
+                continue;
+            }
+            batch_inertia += (w * dist) as f64;
+            batch_weight += w as f64;
+            seen[label] += w as f64;
+            if label < cfg.pinned.len() {
+                continue;
+            }
+            let eta = (w as f64 / seen[label]) as f32;
+            let point = batch.component_tuple(idx);
+            centroids.cx[label] = metric.step_towards(0, centroids.cx[label], point.0, eta);
+            centroids.cy[label] = metric.step_towards(1, centroids.cy[label], point.1, eta);
+            centroids.cz[label] = metric.step_towards(2, centroids.cz[label], point.2, eta);
+        }
+        iterations += 1;
+
+        let shift: f32 = (0..cfg.k)
+            .map(|c| {
+                let (ox, oy, oz) = before.component_tuple(c);
+                let (nx, ny, nz) = centroids.component_tuple(c);
+                metric.distance(ox, oy, oz, nx, ny, nz)
+            })
+            .sum();
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.push(IterationStats {
+                inertia: batch_inertia as f32,
+                shift: shift.sqrt(),
+                reassigned: None,
+                empty_clusters: 0,
+            });
+        }
+        if shift.sqrt() < cfg.tol {
+            return (iterations, true);
+        }
+
+        if batch_weight > 0.0 {
+            let mean = batch_inertia / batch_weight;
+            let current = smoothed.map_or(mean, |s| s + alpha * (mean - s));
+            smoothed = Some(current);
+            if current < best {
+                best = current;
+                stale = 0;
+            } else {
+                stale += 1;
+                if stale >= PATIENCE {
+                    return (iterations, true);
+                }
+            }
+        }
+    }
+    (iterations, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmeans::{run_kmeans, DistanceMode};
+
+    #[test]
+    fn mini_batch_settles_on_the_blob_means() {
+        let mut points = Vec::new();
+        for i in 0..5000 {
+            let t = (i % 11) as f32 * 0.2 - 1.0;
+            points.push([10.0 + t, 10.0 - t, 0.0]);
+            points.push([60.0 - t, 20.0, 40.0 + t]);
+        }
+        let cfg = KMeansConfig {
+            k: 2,
+            max_iters: 500,
+            tol: 1e-4,
+            seed: 3,
+            mini_batch: Some(200),
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &cfg).expect("kmeans");
+        assert!(result.iterations < cfg.max_iters, "never converged");
+        let mut centroids = result.centroids.clone();
+        centroids.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        for (got, want) in centroids
+            .iter()
+            .zip([[10.0, 10.0, 0.0], [60.0, 20.0, 40.0]])
+        {
+            for axis in 0..3 {
+                assert!((got[axis] - want[axis]).abs() < 0.5, "{centroids:?}");
+            }
+        }
+        let mut counts = result.counts;
+        counts.sort_unstable();
+        assert_eq!(counts, vec![5000, 5000]);
+    }
+
+    #[test]
+    fn circular_steps_take_the_short_way_round() {
+        let metric = Metric::new(&KMeansConfig {
+            distance: DistanceMode::Circular {
+                axis: 0,
+                period: 360.0,
+            },
+            ..KMeansConfig::default()
+        })
+        .expect("metric");
+        assert!((metric.step_towards(0, 350.0, 10.0, 0.5) - 0.0).abs() < 1e-3);
+        assert!((metric.step_towards(0, 10.0, 350.0, 0.25) - 5.0).abs() < 1e-3);
+        assert_eq!(metric.step_towards(1, 0.0, 10.0, 0.5), 5.0);
+    }
+}
@@ -0,0 +1,1872 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::Serialize;
+use thiserror::Error;
+#[cfg(feature = "simd")]
+use wide::f32x4;
+
+mod auto_k;
+mod dbscan;
+mod empty;
+mod gaussian;
+mod gmm;
+mod hamerly;
+mod init;
+mod mini_batch;
+mod snap;
+mod spatial;
+
+pub use auto_k::{auto_k, AutoKResult, KCriterion, KScore};
+pub use dbscan::{
+    run_dbscan, run_dbscan_soa, run_dbscan_weighted, DbscanConfig, DbscanResult, NOISE,
+};
+pub use empty::{EmptyClusterReport, EmptyClusterStrategy};
+pub use gmm::{run_gmm, run_gmm_soa, run_gmm_weighted, CovarianceKind, GmmConfig, GmmResult};
+use hamerly::Hamerly;
+pub use init::{KMeansInit, PARALLEL_INIT_MIN_POINTS};
+pub use spatial::{run_spatial_kmeans, run_spatial_kmeans_soa, SpatialConfig, SpatialResult};
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum KMeansError {
+    #[error("no points to cluster")]
+    EmptyData,
+    #[error("k must be > 0")]
+    ZeroK,
+    #[error("k = {k} exceeds the {points} available points")]
+    KTooLarge { k: usize, points: usize },
+    #[error("warm start has {got} centroids but k = {expected}")]
+    WarmStartMismatch { expected: usize, got: usize },
+    #[error("point {index} has a non-finite component")]
+    NonFinite { index: usize },
+    #[error("point {index} has a negative or non-finite weight")]
+    InvalidWeight { index: usize },
+    #[error("{weights} weights for {points} points")]
+    WeightsLengthMismatch { points: usize, weights: usize },
+    #[error("{positions} positions for {points} points")]
+    PositionsLengthMismatch { points: usize, positions: usize },
+    #[error("invalid config: {0}")]
+    InvalidConfig(&'static str),
+}
+
+pub type Result<T> = std::result::Result<T, KMeansError>;
+
+/// How distances and centroid updates treat each component.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DistanceMode {
+    #[default]
+    Euclidean,
+    /// Component `axis` is an angle with the given `period` (360 for hue in degrees).
+    /// That axis contributes the chord length on a circle of circumference `period`,
+    /// which matches the plain difference for small angles, and centroids take the
+    /// circular mean so 359° and 1° average to 0° instead of 180°.
+    Circular { axis: usize, period: f32 },
+}
+
+/// How each iteration finds every point's nearest centroid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AssignmentMode {
+    /// Scan all k centroids for every point.
+    #[default]
+    Lloyd,
+    /// Keep per-point distance bounds (Hamerly) and skip scans they prove
+    /// unnecessary. Same clusters as `Lloyd`; pays off from roughly k ≥ 32.
+    /// Ignored by mini-batch runs, which resample points every iteration.
+    Hamerly,
+}
+
+#[derive(Debug, Clone)]
+pub struct KMeansConfig {
+    pub k: usize,
+    pub max_iters: usize,
+    pub tol: f32,
+    pub seed: u64,
+    /// Starting centroids for the first run. The first `pinned.len()` entries
+    /// are replaced by the pinned centroids.
+    pub warm_start: Option<Vec<[f32; 3]>>,
+    /// Centroids that take part in assignment but never move; they count
+    /// towards `k` and come first in the result.
+    pub pinned: Vec<[f32; 3]>,
+    /// Sculley mini-batch k-means with this many points (drawn with
+    /// replacement) per iteration. Stops on `tol` or once the smoothed batch
+    /// inertia stops improving; counts and inertia come from a final full pass.
+    pub mini_batch: Option<usize>,
+    pub distance: DistanceMode,
+    /// Per-axis weights on the squared distance, e.g. `[0.5, 1.0, 1.0]` to make
+    /// Lab lightness matter half as much as chroma. `None` weighs every axis equally.
+    pub axis_weights: Option<[f32; 3]>,
+    /// Fill [`KMeansResult::labels`] with each point's final cluster index.
+    pub return_labels: bool,
+    pub assignment: AssignmentMode,
+    /// Independent runs from different seeds (in parallel); the lowest-inertia
+    /// one is returned. With `warm_start`, the first run starts from it and the
+    /// rest use [`KMeansConfig::init`]. `0` and `1` both mean a single run.
+    pub n_init: usize,
+    /// Seeding when there is no warm start. Deterministic inits (median cut,
+    /// Wu) seed only the first such run; further restarts use k-means++.
+    pub init: KMeansInit,
+    /// Record per-iteration [`IterationStats`] in [`KMeansResult::trace`].
+    pub trace: bool,
+    /// What to do with a cluster that ends an iteration with no points.
+    /// Mini-batch runs never empty a cluster and ignore this.
+    pub on_empty: EmptyClusterStrategy,
+    /// After fitting, move every unpinned centroid onto its nearest member
+    /// sample so each palette color occurs in the input; counts and inertia
+    /// are then recomputed against the moved centroids.
+    pub snap_to_samples: bool,
+}
+
+impl Default for KMeansConfig {
+    fn default() -> Self {
+        Self {
+            k: 8,
+            max_iters: 40,
+            tol: 1e-3,
+            seed: 1,
+            warm_start: None,
+            pinned: Vec::new(),
+            mini_batch: None,
+            distance: DistanceMode::Euclidean,
+            axis_weights: None,
+            return_labels: false,
+            assignment: AssignmentMode::Lloyd,
+            n_init: 1,
+            init: KMeansInit::Auto,
+            trace: false,
+            on_empty: EmptyClusterStrategy::Random,
+            snap_to_samples: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KMeansResult {
+    pub centroids: Vec<[f32; 3]>,
+    /// Points per cluster; for weighted input, the rounded sum of member weights.
+    pub counts: Vec<usize>,
+    pub iterations: usize,
+    pub inertia: f32,
+    /// Cluster index per input point against the final centroids, when
+    /// [`KMeansConfig::return_labels`] is set.
+    pub labels: Option<Vec<u32>>,
+    /// How the returned run was seeded; `None` when it started from
+    /// [`KMeansConfig::warm_start`] or a spatial grid, or did not come from
+    /// k-means at all.
+    pub init: Option<KMeansInit>,
+    /// Whether the run stopped on its own (centroid shift under `tol`, or a
+    /// mini-batch plateau) rather than by reaching `max_iters`.
+    pub converged: bool,
+    /// One entry per iteration when [`KMeansConfig::trace`] is set.
+    pub trace: Option<Vec<IterationStats>>,
+    /// Set when [`KMeansConfig::on_empty`] fired during the returned run. With
+    /// [`EmptyClusterStrategy::Drop`] there are fewer than k centroids.
+    pub empty_clusters: Option<EmptyClusterReport>,
+    /// With [`KMeansConfig::snap_to_samples`], how far each centroid moved to
+    /// reach its sample (`sqrt` of the configured distance; 0 for pinned ones).
+    pub snap_distances: Option<Vec<f32>>,
+}
+
+/// Diagnostics for one k-means iteration.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IterationStats {
+    /// Weighted inertia against the centroids the iteration started from
+    /// (mini-batch: of that iteration's batch only).
+    pub inertia: f32,
+    /// `sqrt` of the summed squared centroid moves; the value compared to `tol`.
+    pub shift: f32,
+    /// Points whose nearest centroid changed (all of them on the first
+    /// iteration); `None` for mini-batch runs, which resample every iteration.
+    pub reassigned: Option<usize>,
+    /// Clusters that ended the iteration empty and were handled by
+    /// [`KMeansConfig::on_empty`].
+    pub empty_clusters: usize,
+}
+
+/// Fitted centroids plus the distance settings they were fitted with, for
+/// assigning points that were not part of the training set (e.g. every pixel
+/// of the full-resolution image).
+#[derive(Debug, Clone)]
+pub struct KMeansModel {
+    centroids: CentroidsSoa,
+    metric: Metric,
+}
+
+impl KMeansModel {
+    /// `cfg` supplies the distance mode and axis weights; use the config the
+    /// centroids were fitted with so assignments match training.
+    pub fn new(centroids: &[[f32; 3]], cfg: &KMeansConfig) -> Result<Self> {
+        if centroids.is_empty() {
+            return Err(KMeansError::ZeroK);
+        }
+        Ok(Self {
+            centroids: CentroidsSoa::from_vec(centroids),
+            metric: Metric::new(cfg)?,
+        })
+    }
+
+    pub fn centroids(&self) -> Vec<[f32; 3]> {
+        self.centroids.to_vec()
+    }
+
+    /// Index of the nearest centroid for each point.
+    pub fn predict(&self, points: &[[f32; 3]]) -> Vec<u32> {
+        points
+            .par_iter()
+            .map(|p| best_centroid(p[0], p[1], p[2], &self.centroids, &self.metric).0 as u32)
+            .collect()
+    }
+
+    fn predict_soa(&self, points: &PointsSoa) -> Vec<u32> {
+        (0..points.len())
+            .into_par_iter()
+            .map(|idx| nearest_centroid(points, idx, &self.centroids, &self.metric).0 as u32)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PointsSoa {
+    px: Vec<f32>,
+    py: Vec<f32>,
+    pz: Vec<f32>,
+    /// Per-point multiplicity (e.g. pixel counts after dedup). `None` means every point weighs 1.
+    weights: Option<Vec<f32>>,
+    /// Where each point sits in the image, for spatial k-means.
+    positions: Option<Vec<[f32; 2]>>,
+}
+
+impl PointsSoa {
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        let mut px = Vec::with_capacity(points.len());
+        let mut py = Vec::with_capacity(points.len());
+        let mut pz = Vec::with_capacity(points.len());
+        for p in points {
+            px.push(p[0]);
+            py.push(p[1]);
+            pz.push(p[2]);
+        }
+        Self {
+            px,
+            py,
+            pz,
+            weights: None,
+            positions: None,
+        }
+    }
+
+    /// Like [`PointsSoa::from_points`], but each point counts `weights[i]` times
+    /// in sums, counts, inertia and k-means++ seeding.
+    pub fn from_weighted_points(points: &[[f32; 3]], weights: &[f32]) -> Result<Self> {
+        if weights.len() != points.len() {
+            return Err(KMeansError::WeightsLengthMismatch {
+                points: points.len(),
+                weights: weights.len(),
+            });
+        }
+        Ok(Self {
+            weights: Some(weights.to_vec()),
+            ..Self::from_points(points)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.px.len()
+    }
+
+    pub fn weights(&self) -> Option<&[f32]> {
+        self.weights.as_deref()
+    }
+
+    /// Attach `positions[i]` as the image position of point `i`. Only
+    /// [`run_spatial_kmeans_soa`] clusters on them.
+    pub fn with_positions(self, positions: &[[f32; 2]]) -> Result<Self> {
+        if positions.len() != self.len() {
+            return Err(KMeansError::PositionsLengthMismatch {
+                points: self.len(),
+                positions: positions.len(),
+            });
+        }
+        Ok(Self {
+            positions: Some(positions.to_vec()),
+            ..self
+        })
+    }
+
+    pub fn positions(&self) -> Option<&[[f32; 2]]> {
+        self.positions.as_deref()
+    }
+
+    #[inline]
+    fn weight(&self, idx: usize) -> f32 {
+        self.weights.as_ref().map_or(1.0, |w| w[idx])
+    }
+
+    /// `[0, 0]` for points without positions, so the spatial term vanishes.
+    #[inline]
+    fn position(&self, idx: usize) -> [f32; 2] {
+        self.positions.as_ref().map_or([0.0; 2], |p| p[idx])
+    }
+
+    pub fn to_vec(&self) -> Vec<[f32; 3]> {
+        self.px
+            .iter()
+            .zip(self.py.iter())
+            .zip(self.pz.iter())
+            .map(|((x, y), z)| [*x, *y, *z])
+            .collect()
+    }
+
+    pub fn component_tuple(&self, idx: usize) -> (f32, f32, f32) {
+        (self.px[idx], self.py[idx], self.pz[idx])
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CentroidsSoa {
+    cx: Vec<f32>,
+    cy: Vec<f32>,
+    cz: Vec<f32>,
+    /// Mean member position per centroid in a spatial run.
+    positions: Option<Vec<[f32; 2]>>,
+}
+
+impl CentroidsSoa {
+    /// `k` zeroed centroids, with positions when `points` has them.
+    fn with_len(k: usize, points: &PointsSoa) -> Self {
+        Self {
+            cx: vec![0.0; k],
+            cy: vec![0.0; k],
+            cz: vec![0.0; k],
+            positions: points.positions.as_ref().map(|_| vec![[0.0; 2]; k]),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.cx.len()
+    }
+
+    fn remove(&mut self, idx: usize) {
+        self.cx.remove(idx);
+        self.cy.remove(idx);
+        self.cz.remove(idx);
+        if let Some(positions) = self.positions.as_mut() {
+            positions.remove(idx);
+        }
+    }
+
+    fn set_from_soa(&mut self, centroid_idx: usize, points: &PointsSoa, point_idx: usize) {
+        self.cx[centroid_idx] = points.px[point_idx];
+        self.cy[centroid_idx] = points.py[point_idx];
+        self.cz[centroid_idx] = points.pz[point_idx];
+        if let Some(positions) = self.positions.as_mut() {
+            positions[centroid_idx] = points.position(point_idx);
+        }
+    }
+
+    /// The centroids at `keep`, in that order.
+    fn select(&self, keep: &[usize]) -> Self {
+        Self {
+            cx: keep.iter().map(|&idx| self.cx[idx]).collect(),
+            cy: keep.iter().map(|&idx| self.cy[idx]).collect(),
+            cz: keep.iter().map(|&idx| self.cz[idx]).collect(),
+            positions: self
+                .positions
+                .as_ref()
+                .map(|p| keep.iter().map(|&idx| p[idx]).collect()),
+        }
+    }
+
+    fn from_vec(data: &[[f32; 3]]) -> Self {
+        let mut cx = Vec::with_capacity(data.len());
+        let mut cy = Vec::with_capacity(data.len());
+        let mut cz = Vec::with_capacity(data.len());
+        for c in data {
+            cx.push(c[0]);
+            cy.push(c[1]);
+            cz.push(c[2]);
+        }
+        Self {
+            cx,
+            cy,
+            cz,
+            positions: None,
+        }
+    }
+
+    fn component_tuple(&self, idx: usize) -> (f32, f32, f32) {
+        (self.cx[idx], self.cy[idx], self.cz[idx])
+    }
+
+    #[inline]
+    fn position(&self, idx: usize) -> [f32; 2] {
+        self.positions.as_ref().map_or([0.0; 2], |p| p[idx])
+    }
+
+    fn to_vec(&self) -> Vec<[f32; 3]> {
+        self.cx
+            .iter()
+            .zip(self.cy.iter())
+            .zip(self.cz.iter())
+            .map(|((x, y), z)| [*x, *y, *z])
+            .collect()
+    }
+}
+
+pub fn run_kmeans(points: &[[f32; 3]], cfg: &KMeansConfig) -> Result<KMeansResult> {
+    let dataset = PointsSoa::from_points(points);
+    run_kmeans_soa(&dataset, cfg)
+}
+
+/// K-means over deduplicated points where `weights[i]` is how many times
+/// `points[i]` occurs (typically pixel counts from a color histogram).
+pub fn run_kmeans_weighted(
+    points: &[[f32; 3]],
+    weights: &[f32],
+    cfg: &KMeansConfig,
+) -> Result<KMeansResult> {
+    let dataset = PointsSoa::from_weighted_points(points, weights)?;
+    run_kmeans_soa(&dataset, cfg)
+}
+
+pub fn run_kmeans_soa(dataset: &PointsSoa, cfg: &KMeansConfig) -> Result<KMeansResult> {
+    validate(dataset, cfg)?;
+    let metric = Metric::new(cfg)?;
+    Ok(fit(dataset, cfg, metric)?.0)
+}
+
+/// The best of `cfg.n_init` runs, snapped and labelled as configured, plus
+/// its centroids (with their positions in a spatial run).
+fn fit(
+    dataset: &PointsSoa,
+    cfg: &KMeansConfig,
+    metric: Metric,
+) -> Result<(KMeansResult, CentroidsSoa)> {
+    let (mut best, mut centroids) = if cfg.n_init <= 1 {
+        run_once(dataset, cfg, &metric, 0)?
+    } else {
+        let runs: Vec<(KMeansResult, CentroidsSoa)> = (0..cfg.n_init)
+            .into_par_iter()
+            .map(|run| run_once(dataset, cfg, &metric, run))
+            .collect::<Result<_>>()?;
+        // Lowest inertia wins; ties keep the earliest run so the pick is reproducible
+        runs.into_iter()
+            .reduce(|best, next| {
+                if next.0.inertia < best.0.inertia {
+                    next
+                } else {
+                    best
+                }
+            })
+            .expect("n_init > 1")
+    };
+
+    if cfg.snap_to_samples {
+        snap::snap_to_samples(dataset, &mut best, cfg.pinned.len(), &metric);
+        centroids = CentroidsSoa::from_vec(&best.centroids);
+    }
+
+    if cfg.return_labels {
+        let model = KMeansModel {
+            centroids: centroids.clone(),
+            metric,
+        };
+        best.labels = Some(model.predict_soa(dataset));
+    }
+    Ok((best, centroids))
+}
+
+/// Seed for restart `run`; run 0 keeps `cfg.seed` so `n_init = 1` is unchanged.
+fn restart_seed(seed: u64, run: usize) -> u64 {
+    seed.wrapping_add((run as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+fn run_once(
+    dataset: &PointsSoa,
+    cfg: &KMeansConfig,
+    metric: &Metric,
+    run: usize,
+) -> Result<(KMeansResult, CentroidsSoa)> {
+    let metric = *metric;
+    let mut rng = SmallRng::seed_from_u64(restart_seed(cfg.seed, run));
+    let (mut centroids, init) = match &cfg.warm_start {
+        Some(warm) if run == 0 => {
+            let mut start = cfg.pinned.clone();
+            start.extend_from_slice(&warm[cfg.pinned.len()..]);
+            (CentroidsSoa::from_vec(&start), None)
+        }
+        _ if metric.spatial_scale.is_some() => {
+            spatial::seed(dataset, cfg.k, &metric, run, &mut rng)
+        }
+        _ => {
+            let first_seeded = usize::from(cfg.warm_start.is_some());
+            let init = if cfg.init.is_deterministic() && run > first_seeded {
+                KMeansInit::KMeansPlusPlus
+            } else {
+                cfg.init.resolve(dataset.len())
+            };
+            let seeded =
+                init::initial_centroids(dataset, &cfg.pinned, cfg.k, init, &metric, &mut rng)?;
+            (seeded, Some(init))
+        }
+    };
+
+    let mut trace = cfg.trace.then(Vec::new);
+    let mini_batch = cfg
+        .mini_batch
+        .filter(|&batch_size| batch_size > 0 && batch_size < dataset.len());
+    if let Some(batch_size) = mini_batch {
+        let (iterations, converged) = mini_batch::fit(
+            dataset,
+            cfg,
+            &metric,
+            &mut centroids,
+            batch_size,
+            &mut rng,
+            trace.as_mut(),
+        );
+        let (partials, inertia, _) = assignment_step(dataset, &centroids, &metric, None);
+        let result = KMeansResult {
+            centroids: centroids.to_vec(),
+            counts: partials.iter().map(|p| p.weight.round() as usize).collect(),
+            iterations,
+            inertia,
+            labels: None,
+            init,
+            converged,
+            trace,
+            empty_clusters: None,
+            snap_distances: None,
+        };
+        return Ok((result, centroids));
+    }
+
+    let mut counts = vec![0usize; cfg.k];
+    let mut iterations = 0;
+    let mut inertia = 0.0;
+    let mut converged = false;
+    let mut empty_occurrences = 0;
+    // Slots whose refill already held off convergence once; a centroid that
+    // keeps failing to win points must not stall the run
+    let mut retried = vec![false; cfg.k];
+    let mut hamerly = match cfg.assignment {
+        AssignmentMode::Hamerly => Some(Hamerly::new(dataset.len())),
+        AssignmentMode::Lloyd => None,
+    };
+    // Lloyd only needs per-point labels to count reassignments for the trace
+    let mut labels = match (&hamerly, &trace) {
+        (None, Some(_)) => Some(vec![u32::MAX; dataset.len()]),
+        _ => None,
+    };
+
+    while iterations < cfg.max_iters {
+        let (partials, step_inertia, reassigned) = match hamerly.as_mut() {
+            Some(bounds) => bounds.assignment_step(dataset, &centroids, &metric),
+            None => assignment_step(dataset, &centroids, &metric, labels.as_deref_mut()),
+        };
+        inertia = step_inertia;
+        let previous = hamerly.as_ref().map(|_| centroids.clone());
+
+        counts.fill(0);
+        let mut shift = 0.0;
+        let mut empty = Vec::new();
+        let pinned = cfg.pinned.len();
+        for (idx, part) in partials.into_iter().enumerate() {
+            if idx < pinned {
+                counts[idx] = part.weight.round() as usize;
+                continue;
+            }
+            if part.weight <= 0.0 {
+                empty.push(idx);
+                continue;
+            }
+            let inv = 1.0 / part.weight;
+            let mut next = [part.sum_x * inv, part.sum_y * inv, part.sum_z * inv];
+            if let Some(axis) = metric.circular_axis {
+                next[axis] = metric.circular_mean(part.sum_sin, part.sum_cos);
+            }
+            let [nx, ny, nz] = next;
+            let (ox, oy, oz) = centroids.component_tuple(idx);
+            shift += metric.distance(ox, oy, oz, nx, ny, nz);
+            centroids.cx[idx] = nx;
+            centroids.cy[idx] = ny;
+            centroids.cz[idx] = nz;
+            if let Some(positions) = centroids.positions.as_mut() {
+                let moved_to = part.sum_pos.map(|sum| sum * inv);
+                shift += metric.spatial(positions[idx], moved_to);
+                positions[idx] = moved_to;
+            }
+            counts[idx] = part.weight.round() as usize;
+        }
+        if !empty.is_empty() {
+            empty::refill(
+                dataset,
+                &mut centroids,
+                &empty,
+                pinned,
+                cfg.on_empty,
+                &metric,
+                &mut rng,
+            );
+            empty_occurrences += empty.len();
+        }
+        if let (Some(bounds), Some(previous)) = (hamerly.as_mut(), previous.as_ref()) {
+            bounds.centroids_moved(previous, &centroids, &metric);
+        }
+        if cfg.on_empty == EmptyClusterStrategy::Drop {
+            for &idx in empty.iter().rev() {
+                centroids.remove(idx);
+                counts.remove(idx);
+            }
+            if let Some(bounds) = hamerly.as_mut() {
+                bounds.remove_centroids(&empty);
+            }
+            if let Some(labels) = labels.as_mut() {
+                relabel_after_removal(labels, &empty);
+            }
+            for &idx in empty.iter().rev() {
+                retried.remove(idx);
+            }
+        }
+
+        iterations += 1;
+        if let Some(trace) = trace.as_mut() {
+            trace.push(IterationStats {
+                inertia,
+                shift: shift.sqrt(),
+                reassigned: Some(reassigned),
+                empty_clusters: empty.len(),
+            });
+        }
+        // A freshly re-placed centroid has not been fitted yet, so keep going,
+        // but only once per slot
+        let mut refilled = false;
+        if cfg.on_empty != EmptyClusterStrategy::Drop {
+            for &idx in &empty {
+                refilled |= !retried[idx];
+                retried[idx] = true;
+            }
+        }
+        if shift.sqrt() < cfg.tol && !refilled {
+            converged = true;
+            break;
+        }
+    }
+
+    let result = KMeansResult {
+        centroids: centroids.to_vec(),
+        counts,
+        iterations,
+        inertia,
+        labels: None,
+        init,
+        converged,
+        trace,
+        empty_clusters: (empty_occurrences > 0).then_some(EmptyClusterReport {
+            strategy: cfg.on_empty,
+            occurrences: empty_occurrences,
+        }),
+        snap_distances: None,
+    };
+    Ok((result, centroids))
+}
+
+/// Shift labels down past removed (empty, so unused) cluster indices.
+fn relabel_after_removal(labels: &mut [u32], removed: &[usize]) {
+    labels.par_iter_mut().for_each(|label| {
+        if *label != u32::MAX {
+            *label -= removed.iter().filter(|&&r| (r as u32) < *label).count() as u32;
+        }
+    });
+}
+
+fn validate(dataset: &PointsSoa, cfg: &KMeansConfig) -> Result<()> {
+    if dataset.len() == 0 {
+        return Err(KMeansError::EmptyData);
+    }
+    if cfg.k == 0 {
+        return Err(KMeansError::ZeroK);
+    }
+    if cfg.k > dataset.len() {
+        return Err(KMeansError::KTooLarge {
+            k: cfg.k,
+            points: dataset.len(),
+        });
+    }
+    if let Some(warm) = &cfg.warm_start {
+        if warm.len() != cfg.k {
+            return Err(KMeansError::WarmStartMismatch {
+                expected: cfg.k,
+                got: warm.len(),
+            });
+        }
+        if warm.iter().flatten().any(|v| !v.is_finite()) {
+            return Err(KMeansError::InvalidConfig("warm start must be finite"));
+        }
+    }
+    if cfg.pinned.len() > cfg.k {
+        return Err(KMeansError::InvalidConfig("more pinned centroids than k"));
+    }
+    if cfg.pinned.iter().flatten().any(|v| !v.is_finite()) {
+        return Err(KMeansError::InvalidConfig(
+            "pinned centroids must be finite",
+        ));
+    }
+    let finite = |idx: usize| {
+        let (x, y, z) = dataset.component_tuple(idx);
+        x.is_finite() && y.is_finite() && z.is_finite()
+    };
+    if let Some(index) = (0..dataset.len()).find(|&idx| !finite(idx)) {
+        return Err(KMeansError::NonFinite { index });
+    }
+    if let Some(positions) = dataset.positions() {
+        if let Some(index) = positions
+            .iter()
+            .position(|p| !p.iter().all(|v| v.is_finite()))
+        {
+            return Err(KMeansError::NonFinite { index });
+        }
+    }
+    if let Some(weights) = dataset.weights() {
+        if let Some(index) = weights.iter().position(|w| !w.is_finite() || *w < 0.0) {
+            return Err(KMeansError::InvalidWeight { index });
+        }
+    }
+    Ok(())
+}
+
+/// Distance evaluation resolved from [`DistanceMode`] and axis weights once per run.
+#[derive(Debug, Clone, Copy)]
+struct Metric {
+    circular_axis: Option<usize>,
+    period: f32,
+    /// Radians per unit along the circular axis.
+    angle_scale: f32,
+    /// `2r²` for a circle of radius `r = period / 2π`; chord² = `2r²(1 - cos Δθ)`.
+    chord_scale: f32,
+    /// Per-axis multipliers on the squared difference; all 1.0 unless `weighted`.
+    weights: [f32; 3],
+    weighted: bool,
+    /// Multiplier on the squared gap between image positions in a spatial
+    /// run; positions are ignored when `None`.
+    spatial_scale: Option<f32>,
+}
+
+impl Metric {
+    fn new(cfg: &KMeansConfig) -> Result<Self> {
+        let (weights, weighted) = match cfg.axis_weights {
+            Some(w) => {
+                if !w.iter().all(|v| v.is_finite() && *v >= 0.0) {
+                    return Err(KMeansError::InvalidConfig(
+                        "axis weights must be finite and >= 0",
+                    ));
+                }
+                (w, true)
+            }
+            None => ([1.0; 3], false),
+        };
+        let metric = match cfg.distance {
+            DistanceMode::Euclidean => Self {
+                circular_axis: None,
+                period: 0.0,
+                angle_scale: 0.0,
+                chord_scale: 0.0,
+                weights,
+                weighted,
+                spatial_scale: None,
+            },
+            DistanceMode::Circular { axis, period } => {
+                if axis >= 3 {
+                    return Err(KMeansError::InvalidConfig(
+                        "circular axis must be 0, 1 or 2",
+                    ));
+                }
+                if !(period > 0.0 && period.is_finite()) {
+                    return Err(KMeansError::InvalidConfig("circular period must be > 0"));
+                }
+                let radius = period / std::f32::consts::TAU;
+                Self {
+                    circular_axis: Some(axis),
+                    period,
+                    angle_scale: std::f32::consts::TAU / period,
+                    chord_scale: 2.0 * radius * radius,
+                    weights,
+                    weighted,
+                    spatial_scale: None,
+                }
+            }
+        };
+        Ok(metric)
+    }
+
+    #[inline]
+    fn chord_squared(&self, delta: f32) -> f32 {
+        self.chord_scale * (1.0 - (delta * self.angle_scale).cos())
+    }
+
+    #[inline]
+    fn distance(&self, px: f32, py: f32, pz: f32, cx: f32, cy: f32, cz: f32) -> f32 {
+        if self.circular_axis.is_none() && !self.weighted {
+            return squared_distance_components(px, py, pz, cx, cy, cz);
+        }
+        let deltas = [px - cx, py - cy, pz - cz];
+        let mut total = 0.0;
+        for (axis, d) in deltas.into_iter().enumerate() {
+            let sq = if self.circular_axis == Some(axis) {
+                self.chord_squared(d)
+            } else {
+                d * d
+            };
+            total += self.weights[axis] * sq;
+        }
+        total
+    }
+
+    /// Weighted squared gap between two image positions; 0 unless spatial.
+    #[inline]
+    fn spatial(&self, a: [f32; 2], b: [f32; 2]) -> f32 {
+        match self.spatial_scale {
+            Some(scale) => {
+                let dx = a[0] - b[0];
+                let dy = a[1] - b[1];
+                scale * (dx * dx + dy * dy)
+            }
+            None => 0.0,
+        }
+    }
+
+    /// Squared distance from point `idx` to centroid `c`, position included.
+    #[inline]
+    fn point_to_centroid(
+        &self,
+        points: &PointsSoa,
+        idx: usize,
+        centroids: &CentroidsSoa,
+        c: usize,
+    ) -> f32 {
+        let (px, py, pz) = points.component_tuple(idx);
+        let (cx, cy, cz) = centroids.component_tuple(c);
+        self.distance(px, py, pz, cx, cy, cz)
+            + self.spatial(points.position(idx), centroids.position(c))
+    }
+
+    /// Squared distance between centroid `a` of `from` and centroid `b` of
+    /// `to`, position included.
+    #[inline]
+    fn between(&self, from: &CentroidsSoa, a: usize, to: &CentroidsSoa, b: usize) -> f32 {
+        let (ax, ay, az) = from.component_tuple(a);
+        let (bx, by, bz) = to.component_tuple(b);
+        self.distance(ax, ay, az, bx, by, bz) + self.spatial(from.position(a), to.position(b))
+    }
+
+    /// `to - from` along `axis`, the short way round on the circular axis.
+    #[inline]
+    fn axis_delta(&self, axis: usize, from: f32, to: f32) -> f32 {
+        if self.circular_axis != Some(axis) {
+            return to - from;
+        }
+        let half = self.period / 2.0;
+        (to - from + half).rem_euclid(self.period) - half
+    }
+
+    /// `value + delta` along `axis`, wrapped into `[0, period)` on the circular axis.
+    #[inline]
+    fn offset(&self, axis: usize, value: f32, delta: f32) -> f32 {
+        if self.circular_axis == Some(axis) {
+            (value + delta).rem_euclid(self.period)
+        } else {
+            value + delta
+        }
+    }
+
+    /// Move `from` the fraction `eta` of the way to `to` along `axis`, the
+    /// short way round on the circular axis.
+    #[inline]
+    fn step_towards(&self, axis: usize, from: f32, to: f32, eta: f32) -> f32 {
+        self.offset(axis, from, eta * self.axis_delta(axis, from, to))
+    }
+
+    /// Circular mean from summed unit vectors, mapped into `[0, period)`.
+    fn circular_mean(&self, sum_sin: f32, sum_cos: f32) -> f32 {
+        (sum_sin.atan2(sum_cos) / self.angle_scale).rem_euclid(self.period)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ClusterPartial {
+    sum_x: f32,
+    sum_y: f32,
+    sum_z: f32,
+    /// Unit-vector sums for the circular axis (zero when the metric is Euclidean).
+    sum_sin: f32,
+    sum_cos: f32,
+    /// Member position sums (zero unless the run is spatial).
+    sum_pos: [f32; 2],
+    /// Total member weight (the member count for unweighted input).
+    weight: f32,
+}
+
+impl ClusterPartial {
+    #[inline]
+    fn add(&mut self, px: f32, py: f32, pz: f32, pos: [f32; 2], w: f32, metric: &Metric) {
+        self.sum_x += px * w;
+        self.sum_y += py * w;
+        self.sum_z += pz * w;
+        if let Some(axis) = metric.circular_axis {
+            let angle = [px, py, pz][axis] * metric.angle_scale;
+            let (sin, cos) = angle.sin_cos();
+            self.sum_sin += sin * w;
+            self.sum_cos += cos * w;
+        }
+        if metric.spatial_scale.is_some() {
+            self.sum_pos[0] += pos[0] * w;
+            self.sum_pos[1] += pos[1] * w;
+        }
+        self.weight += w;
+    }
+}
+
+/// Points per parallel work unit. Every assignment mode must chunk the same
+/// way so per-chunk f32 sums, and therefore centroids, match bit for bit.
+fn assignment_chunk_size(k: usize) -> usize {
+    1024usize.max(k)
+}
+
+/// Nearest-centroid pass over every point. With `labels`, each point's
+/// previous assignment is read and overwritten (`u32::MAX` for none) and the
+/// third value counts the points whose assignment changed; otherwise it is 0.
+fn assignment_step(
+    points: &PointsSoa,
+    centroids: &CentroidsSoa,
+    metric: &Metric,
+    labels: Option<&mut [u32]>,
+) -> (Vec<ClusterPartial>, f32, usize) {
+    let k = centroids.len();
+    let chunk_size = assignment_chunk_size(k);
+    let total_len = points.len();
+    let chunk_count = total_len.div_ceil(chunk_size);
+    let label_chunks: Vec<Option<&mut [u32]>> = match labels {
+        Some(labels) => labels.chunks_mut(chunk_size).map(Some).collect(),
+        None => (0..chunk_count).map(|_| None).collect(),
+    };
+
+    let (chunk_partials, reassigned): (Vec<(Vec<ClusterPartial>, f32)>, Vec<usize>) = label_chunks
+        .into_par_iter()
+        .enumerate()
+        .map(|(chunk_idx, mut labels)| {
+            let start = chunk_idx * chunk_size;
+            let end = ((chunk_idx + 1) * chunk_size).min(total_len);
+            let mut partials = vec![ClusterPartial::default(); k];
+            let mut inertia = 0.0f32;
+            let mut reassigned = 0;
+            for idx in start..end {
+                let (px, py, pz) = points.component_tuple(idx);
+                let pos = points.position(idx);
+                let w = points.weight(idx);
+                let (best_idx, best_dist) = nearest_centroid(points, idx, centroids, metric);
+                partials[best_idx].add(px, py, pz, pos, w, metric);
+                inertia += best_dist * w;
+                if let Some(labels) = labels.as_deref_mut() {
+                    let label = &mut labels[idx - start];
+                    if *label != best_idx as u32 {
+                        *label = best_idx as u32;
+                        reassigned += 1;
+                    }
+                }
+            }
+            ((partials, inertia), reassigned)
+        })
+        .unzip();
+
+    let (partials, inertia) = merge_partials(chunk_partials, k);
+    (partials, inertia, reassigned.iter().sum())
+}
+
+fn merge_partials(
+    chunk_partials: Vec<(Vec<ClusterPartial>, f32)>,
+    k: usize,
+) -> (Vec<ClusterPartial>, f32) {
+    // Deterministic, numerically steadier merge: accumulate in f64, fixed order
+    let mut totals = vec![ClusterPartial::default(); k];
+    let mut acc_x: Vec<f64> = vec![0.0; k];
+    let mut acc_y: Vec<f64> = vec![0.0; k];
+    let mut acc_z: Vec<f64> = vec![0.0; k];
+    let mut acc_sin: Vec<f64> = vec![0.0; k];
+    let mut acc_cos: Vec<f64> = vec![0.0; k];
+    let mut acc_pos: Vec<[f64; 2]> = vec![[0.0; 2]; k];
+    let mut acc_w: Vec<f64> = vec![0.0; k];
+    let mut total_inertia = 0.0f32;
+    for (chunk_partials, chunk_inertia) in chunk_partials {
+        for idx in 0..k {
+            acc_x[idx] += chunk_partials[idx].sum_x as f64;
+            acc_y[idx] += chunk_partials[idx].sum_y as f64;
+            acc_z[idx] += chunk_partials[idx].sum_z as f64;
+            acc_sin[idx] += chunk_partials[idx].sum_sin as f64;
+            acc_cos[idx] += chunk_partials[idx].sum_cos as f64;
+            acc_pos[idx][0] += chunk_partials[idx].sum_pos[0] as f64;
+            acc_pos[idx][1] += chunk_partials[idx].sum_pos[1] as f64;
+            acc_w[idx] += chunk_partials[idx].weight as f64;
+        }
+        total_inertia += chunk_inertia;
+    }
+    for idx in 0..k {
+        totals[idx].sum_x = acc_x[idx] as f32;
+        totals[idx].sum_y = acc_y[idx] as f32;
+        totals[idx].sum_z = acc_z[idx] as f32;
+        totals[idx].sum_sin = acc_sin[idx] as f32;
+        totals[idx].sum_cos = acc_cos[idx] as f32;
+        totals[idx].sum_pos = acc_pos[idx].map(|v| v as f32);
+        totals[idx].weight = acc_w[idx] as f32;
+    }
+
+    (totals, total_inertia)
+}
+
+/// Nearest centroid to a color that has no image position; callers in a
+/// spatial run use [`nearest_centroid`].
+#[inline]
+fn best_centroid(
+    px: f32,
+    py: f32,
+    pz: f32,
+    centroids: &CentroidsSoa,
+    metric: &Metric,
+) -> (usize, f32) {
+    #[cfg(feature = "simd")]
+    let (idx, dist, _) = best_centroid_simd::<false>(px, py, pz, [0.0; 2], centroids, metric);
+    #[cfg(not(feature = "simd"))]
+    let (idx, dist, _) = best_centroid_scalar::<false>(px, py, pz, [0.0; 2], centroids, metric);
+    (idx, dist)
+}
+
+/// Nearest centroid to point `idx`, counting its position in a spatial run.
+#[inline]
+fn nearest_centroid(
+    points: &PointsSoa,
+    idx: usize,
+    centroids: &CentroidsSoa,
+    metric: &Metric,
+) -> (usize, f32) {
+    let (px, py, pz) = points.component_tuple(idx);
+    let pos = points.position(idx);
+    #[cfg(feature = "simd")]
+    let (idx, dist, _) = best_centroid_simd::<false>(px, py, pz, pos, centroids, metric);
+    #[cfg(not(feature = "simd"))]
+    let (idx, dist, _) = best_centroid_scalar::<false>(px, py, pz, pos, centroids, metric);
+    (idx, dist)
+}
+
+/// Like [`nearest_centroid`] (same winner, same distance) but also returns
+/// the runner-up's distance, `f32::MAX` when there is only one centroid.
+#[inline]
+fn best_two_centroids(
+    points: &PointsSoa,
+    idx: usize,
+    centroids: &CentroidsSoa,
+    metric: &Metric,
+) -> (usize, f32, f32) {
+    #[cfg(not(feature = "simd"))]
+    use best_centroid_scalar as search;
+    #[cfg(feature = "simd")]
+    use best_centroid_simd as search;
+    let (px, py, pz) = points.component_tuple(idx);
+    search::<true>(px, py, pz, points.position(idx), centroids, metric)
+}
+
+/// `pos` is the point's image position; it only counts in a spatial run.
+#[cfg(feature = "simd")]
+fn best_centroid_simd<const SECOND: bool>(
+    px: f32,
+    py: f32,
+    pz: f32,
+    pos: [f32; 2],
+    centroids: &CentroidsSoa,
+    metric: &Metric,
+) -> (usize, f32, f32) {
+    const LANES: usize = 4;
+    let mut best_idx = 0;
+    let mut best_dist = f32::MAX;
+    let mut second_dist = f32::MAX;
+    let len = centroids.len();
+    let px_v = f32x4::splat(px);
+    let py_v = f32x4::splat(py);
+    let pz_v = f32x4::splat(pz);
+
+    let mut idx = 0;
+    while idx + LANES <= len {
+        let cx = f32x4::from([
+            centroids.cx[idx],
+            centroids.cx[idx + 1],
+            centroids.cx[idx + 2],
+            centroids.cx[idx + 3],
+        ]);
+        let cy = f32x4::from([
+            centroids.cy[idx],
+            centroids.cy[idx + 1],
+            centroids.cy[idx + 2],
+            centroids.cy[idx + 3],
+        ]);
+        let cz = f32x4::from([
+            centroids.cz[idx],
+            centroids.cz[idx + 1],
+            centroids.cz[idx + 2],
+            centroids.cz[idx + 3],
+        ]);
+
+        let dx = px_v - cx;
+        let dy = py_v - cy;
+        let dz = pz_v - cz;
+        let mut sq = [dx * dx, dy * dy, dz * dz];
+        if let Some(axis) = metric.circular_axis {
+            let delta = [dx, dy, dz][axis];
+            let cos = (delta * f32x4::splat(metric.angle_scale)).cos();
+            sq[axis] = f32x4::splat(metric.chord_scale) * (f32x4::ONE - cos);
+        }
+        if metric.weighted {
+            for (axis, v) in sq.iter_mut().enumerate() {
+                *v *= f32x4::splat(metric.weights[axis]);
+            }
+        }
+        let mut dist = sq[0] + sq[1] + sq[2];
+        if let (Some(scale), Some(positions)) = (metric.spatial_scale, &centroids.positions) {
+            let lanes = &positions[idx..idx + LANES];
+            let qx = f32x4::from([lanes[0][0], lanes[1][0], lanes[2][0], lanes[3][0]]);
+            let qy = f32x4::from([lanes[0][1], lanes[1][1], lanes[2][1], lanes[3][1]]);
+            let dx = f32x4::splat(pos[0]) - qx;
+            let dy = f32x4::splat(pos[1]) - qy;
+            dist += f32x4::splat(scale) * (dx * dx + dy * dy);
+        }
+        let dist_arr: [f32; LANES] = dist.into();
+        for lane in 0..LANES {
+            let d = dist_arr[lane];
+            if d < best_dist {
+                if SECOND {
+                    second_dist = best_dist;
+                }
+                best_dist = d;
+                best_idx = idx + lane;
+            } else if SECOND && d < second_dist {
+                second_dist = d;
+            }
+        }
+        idx += LANES;
+    }
+
+    while idx < len {
+        let d = metric.distance(
+            px,
+            py,
+            pz,
+            centroids.cx[idx],
+            centroids.cy[idx],
+            centroids.cz[idx],
+        ) + metric.spatial(pos, centroids.position(idx));
+        if d < best_dist {
+            if SECOND {
+                second_dist = best_dist;
+            }
+            best_dist = d;
+            best_idx = idx;
+        } else if SECOND && d < second_dist {
+            second_dist = d;
+        }
+        idx += 1;
+    }
+    (best_idx, best_dist, second_dist)
+}
+
+#[cfg(not(feature = "simd"))]
+fn best_centroid_scalar<const SECOND: bool>(
+    px: f32,
+    py: f32,
+    pz: f32,
+    pos: [f32; 2],
+    centroids: &CentroidsSoa,
+    metric: &Metric,
+) -> (usize, f32, f32) {
+    let mut best_idx = 0usize;
+    let mut best_dist = f32::MAX;
+    let mut second_dist = f32::MAX;
+    for i in 0..centroids.len() {
+        let d = metric.distance(
+            px,
+            py,
+            pz,
+            centroids.cx[i],
+            centroids.cy[i],
+            centroids.cz[i],
+        ) + metric.spatial(pos, centroids.position(i));
+        if d < best_dist {
+            if SECOND {
+                second_dist = best_dist;
+            }
+            best_dist = d;
+            best_idx = i;
+        } else if SECOND && d < second_dist {
+            second_dist = d;
+        }
+    }
+    (best_idx, best_dist, second_dist)
+}
+
+/// D²-weighted seeding of `k` centroids; the first `fixed.len()` are `fixed`
+/// and only the rest are drawn.
+fn kmeans_plus_plus(
+    points: &PointsSoa,
+    fixed: &[[f32; 3]],
+    k: usize,
+    metric: &Metric,
+    rng: &mut SmallRng,
+) -> CentroidsSoa {
+    let n = points.len();
+    let mut centroids = CentroidsSoa::with_len(k, points);
+    let mut chosen_flags = vec![false; n];
+    if fixed.is_empty() {
+        // Weighted input draws the first seed proportional to weight, then weight × D²
+        let first_idx = match points.weights() {
+            Some(weights) => {
+                let total: f32 = weights.iter().sum();
+                if total > 0.0 {
+                    pick_proportional(weights.iter().copied(), rng.gen::<f32>() * total)
+                } else {
+                    rng.gen_range(0..n)
+                }
+            }
+            None => rng.gen_range(0..n),
+        };
+        centroids.set_from_soa(0, points, first_idx);
+        chosen_flags[first_idx] = true;
+    } else {
+        for (idx, &[x, y, z]) in fixed.iter().enumerate() {
+            centroids.cx[idx] = x;
+            centroids.cy[idx] = y;
+            centroids.cz[idx] = z;
+        }
+    }
+    let start = fixed.len().max(1);
+
+    let mut distances = vec![f32::INFINITY; n];
+    for c in 0..start {
+        for (i, dist) in distances.iter_mut().enumerate() {
+            let d = metric.point_to_centroid(points, i, &centroids, c) * points.weight(i);
+            *dist = dist.min(d);
+        }
+    }
+
+    for centroid_idx in start..k {
+        let mut sum = 0.0;
+        for (i, dist) in distances.iter().enumerate() {
+            if !chosen_flags[i] {
+                sum += *dist;
+            }
+        }
+
+        let chosen_idx = if sum == 0.0 {
+            // Fallback: pick a random unchosen point
+            let mut idx;
+            loop {
+                idx = rng.gen_range(0..n);
+                if !chosen_flags[idx] {
+                    break;
+                }
+            }
+            idx
+        } else {
+            let target = rng.gen::<f32>() * sum;
+            let masked = distances
+                .iter()
+                .zip(chosen_flags.iter())
+                .map(|(d, &chosen)| if chosen { 0.0 } else { *d });
+            pick_proportional(masked, target)
+        };
+
+        centroids.set_from_soa(centroid_idx, points, chosen_idx);
+        chosen_flags[chosen_idx] = true;
+
+        for i in 0..n {
+            if chosen_flags[i] {
+                distances[i] = 0.0;
+                continue;
+            }
+            let dist =
+                metric.point_to_centroid(points, i, &centroids, centroid_idx) * points.weight(i);
+            if dist < distances[i] {
+                distances[i] = dist;
+            }
+        }
+    }
+
+    centroids
+}
+
+/// Indices of `count` points chosen one at a time, each with the largest
+/// weighted distance to its nearest centroid among `existing` and the earlier
+/// picks. Ties go to the lowest index so the choice is deterministic.
+fn farthest_points(
+    dataset: &PointsSoa,
+    existing: &CentroidsSoa,
+    count: usize,
+    metric: &Metric,
+) -> Vec<usize> {
+    debug_assert!(existing.len() > 0);
+    let mut distances: Vec<f32> = (0..dataset.len())
+        .into_par_iter()
+        .map(|idx| nearest_centroid(dataset, idx, existing, metric).1 * dataset.weight(idx))
+        .collect();
+    let mut picks = Vec::with_capacity(count);
+    while picks.len() < count {
+        let (far, _) = distances
+            .iter()
+            .enumerate()
+            .fold(
+                (0, f32::MIN),
+                |best, (idx, &d)| if d > best.1 { (idx, d) } else { best },
+            );
+        picks.push(far);
+        let (fx, fy, fz) = dataset.component_tuple(far);
+        let far_pos = dataset.position(far);
+        distances
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, dist)| {
+                let (px, py, pz) = dataset.component_tuple(idx);
+                let d = metric.distance(px, py, pz, fx, fy, fz)
+                    + metric.spatial(dataset.position(idx), far_pos);
+                *dist = dist.min(d * dataset.weight(idx));
+            });
+        distances[far] = f32::MIN;
+    }
+    picks
+}
+
+/// Index where the running sum of `masses` first reaches `target`.
+fn pick_proportional(masses: impl Iterator<Item = f32>, mut target: f32) -> usize {
+    let mut last_positive = 0;
+    for (i, mass) in masses.enumerate() {
+        if mass <= 0.0 {
+            continue;
+        }
+        last_positive = i;
+        target -= mass;
+        if target <= 0.0 {
+            return i;
+        }
+    }
+    // Float round-off left a sliver of target: take the last point that had mass
+    last_positive
+}
+
+fn sample_batch(points: &PointsSoa, size: usize, rng: &mut SmallRng) -> PointsSoa {
+    if size == 0 {
+        return PointsSoa {
+            px: Vec::new(),
+            py: Vec::new(),
+            pz: Vec::new(),
+            weights: None,
+            positions: None,
+        };
+    }
+    if size >= points.len() {
+        return points.clone();
+    }
+    let mut px = Vec::with_capacity(size);
+    let mut py = Vec::with_capacity(size);
+    let mut pz = Vec::with_capacity(size);
+    let mut weights = points.weights.as_ref().map(|_| Vec::with_capacity(size));
+    for _ in 0..size {
+        let idx = rng.gen_range(0..points.len());
+        px.push(points.px[idx]);
+        py.push(points.py[idx]);
+        pz.push(points.pz[idx]);
+        if let Some(w) = weights.as_mut() {
+            w.push(points.weight(idx));
+        }
+    }
+    PointsSoa {
+        px,
+        py,
+        pz,
+        weights,
+        positions: None,
+    }
+}
+
+#[inline]
+fn squared_distance_components(px: f32, py: f32, pz: f32, cx: f32, cy: f32, cz: f32) -> f32 {
+    let dx = px - cx;
+    let dy = py - cy;
+    let dz = pz - cz;
+    dx * dx + dy * dy + dz * dz
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_cluster_data_parallel() {
+        let mut points = Vec::new();
+        for _ in 0..200 {
+            points.push([0.05, 0.02, 0.01]);
+            points.push([0.9, 0.8, 0.75]);
+        }
+        let cfg = KMeansConfig {
+            k: 2,
+            max_iters: 20,
+            tol: 1e-4,
+            seed: 42,
+            warm_start: None,
+            mini_batch: None,
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &cfg).expect("kmeans");
+        assert_eq!(result.centroids.len(), 2);
+        assert!(result.counts.iter().all(|&c| c > 0));
+        assert!(result.iterations <= 20);
+        assert!(result.inertia < 5.0);
+    }
+
+    #[test]
+    fn warm_start_respected() {
+        let points = vec![[0.0, 0.0, 0.0]; 10];
+        let cfg = KMeansConfig {
+            k: 1,
+            max_iters: 5,
+            tol: 1e-6,
+            seed: 2,
+            warm_start: Some(vec![[0.5, 0.5, 0.5]]),
+            mini_batch: None,
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &cfg).expect("kmeans");
+        assert_eq!(result.centroids[0], [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn mini_batch_executes() {
+        let mut points = Vec::new();
+        for _ in 0..1000 {
+            points.push([0.1, 0.2, 0.3]);
+            points.push([0.9, 0.8, 0.7]);
+        }
+        let cfg = KMeansConfig {
+            k: 2,
+            max_iters: 5,
+            tol: 1e-3,
+            seed: 7,
+            warm_start: None,
+            mini_batch: Some(256),
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &cfg).expect("kmeans");
+        assert_eq!(result.centroids.len(), 2);
+    }
+
+    #[test]
+    fn determinism_across_runs() {
+        // Build a deterministic dataset
+        let mut points = Vec::new();
+        for i in 0..500 {
+            let t = (i as f32) / 500.0;
+            points.push([0.1 + 0.2 * t, 0.3 + 0.1 * t, 0.5 + 0.05 * t]);
+            points.push([0.8 - 0.1 * t, 0.7 - 0.2 * t, 0.6 - 0.1 * t]);
+        }
+        let cfg = KMeansConfig {
+            k: 6,
+            max_iters: 30,
+            tol: 1e-6,
+            seed: 12345,
+            warm_start: None,
+            mini_batch: None,
+            ..KMeansConfig::default()
+        };
+        let r1 = run_kmeans(&points, &cfg).expect("kmeans");
+        let r2 = run_kmeans(&points, &cfg).expect("kmeans");
+        assert_eq!(r1.counts, r2.counts);
+        assert_eq!(r1.iterations, r2.iterations);
+        for (a, b) in r1.centroids.iter().zip(r2.centroids.iter()) {
+            for j in 0..3 {
+                let diff = (a[j] - b[j]).abs();
+                assert!(diff <= 1e-6, "centroid component diff {} > 1e-6", diff);
+            }
+        }
+    }
+
+    #[test]
+    fn circular_hue_wraps_around_zero() {
+        // Reds straddling 0°/360° plus a cyan group, in HSL-like [h, s, l] coordinates
+        let mut points = Vec::new();
+        for i in 0..50 {
+            let jitter = (i % 5) as f32;
+            points.push([358.0 - jitter, 0.8, 0.5]);
+            points.push([2.0 + jitter, 0.8, 0.5]);
+            points.push([180.0 + jitter, 0.8, 0.5]);
+        }
+        let cfg = KMeansConfig {
+            k: 2,
+            max_iters: 30,
+            tol: 1e-4,
+            seed: 3,
+            distance: DistanceMode::Circular {
+                axis: 0,
+                period: 360.0,
+            },
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &cfg).expect("kmeans");
+        let mut counts = result.counts.clone();
+        counts.sort_unstable();
+        assert_eq!(counts, vec![50, 100]);
+        let red_idx = if result.counts[0] == 100 { 0 } else { 1 };
+        let hue = result.centroids[red_idx][0];
+        let off = hue.min(360.0 - hue);
+        assert!(off < 0.5, "red centroid hue {hue} should sit at ~0°");
+    }
+
+    #[test]
+    fn chord_distance_matches_small_differences() {
+        let metric = Metric::new(&KMeansConfig {
+            distance: DistanceMode::Circular {
+                axis: 0,
+                period: 360.0,
+            },
+            ..KMeansConfig::default()
+        })
+        .expect("metric");
+        let near = metric.distance(359.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        assert!((near - 4.0).abs() < 0.01, "got {near}");
+        let opposite = metric.distance(0.0, 0.0, 0.0, 180.0, 0.0, 0.0);
+        let diameter = 360.0 / std::f32::consts::PI;
+        assert!((opposite - diameter * diameter).abs() < 1.0);
+    }
+
+    #[test]
+    fn axis_weights_change_the_split() {
+        // Four corners of a rectangle: wide in x, narrow in y
+        let mut points = Vec::new();
+        for _ in 0..25 {
+            points.push([0.0, 0.0, 0.0]);
+            points.push([0.0, 3.0, 0.0]);
+            points.push([10.0, 0.0, 0.0]);
+            points.push([10.0, 3.0, 0.0]);
+        }
+        let split_axis = |weights: Option<[f32; 3]>| {
+            let cfg = KMeansConfig {
+                k: 2,
+                seed: 5,
+                warm_start: Some(vec![[0.0, 0.0, 0.0], [10.0, 3.0, 0.0]]),
+                axis_weights: weights,
+                ..KMeansConfig::default()
+            };
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            let [a, b] = [result.centroids[0], result.centroids[1]];
+            if (a[0] - b[0]).abs() > (a[1] - b[1]).abs() {
+                0
+            } else {
+                1
+            }
+        };
+        assert_eq!(split_axis(None), 0);
+        assert_eq!(split_axis(Some([0.01, 1.0, 1.0])), 1);
+    }
+
+    #[test]
+    fn weighted_distance_scales_each_axis() {
+        let metric = Metric::new(&KMeansConfig {
+            axis_weights: Some([0.5, 2.0, 0.0]),
+            ..KMeansConfig::default()
+        })
+        .expect("metric");
+        let d = metric.distance(2.0, 1.0, 7.0, 0.0, 0.0, 0.0);
+        assert!((d - (0.5 * 4.0 + 2.0 * 1.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn labels_and_predict_agree_with_counts() {
+        let mut points = Vec::new();
+        for i in 0..300 {
+            let t = (i % 10) as f32 * 0.01;
+            points.push([0.1 + t, 0.2, 0.3]);
+            points.push([0.9 - t, 0.8, 0.7]);
+            points.push([0.5, 0.1 + t, 0.9]);
+        }
+        let cfg = KMeansConfig {
+            k: 3,
+            seed: 11,
+            return_labels: true,
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &cfg).expect("kmeans");
+        let labels = result.labels.clone().expect("labels requested");
+        assert_eq!(labels.len(), points.len());
+        let mut tally = vec![0usize; cfg.k];
+        for &label in &labels {
+            tally[label as usize] += 1;
+        }
+        assert_eq!(tally, result.counts);
+
+        let model = KMeansModel::new(&result.centroids, &cfg).expect("model");
+        assert_eq!(model.predict(&points), labels);
+        let unlabeled = KMeansConfig {
+            return_labels: false,
+            ..cfg
+        };
+        let result = run_kmeans(&points, &unlabeled).expect("kmeans");
+        assert!(result.labels.is_none());
+    }
+
+    #[test]
+    fn restarts_keep_the_lowest_inertia_run() {
+        let mut points = Vec::new();
+        for i in 0..400 {
+            let t = (i % 20) as f32 * 0.05;
+            points.push([t, 0.0, 0.0]);
+            points.push([5.0 + t, 5.0, 0.0]);
+            points.push([0.0, 5.0 + t, 5.0]);
+            points.push([5.0, 0.0, 5.0 + t]);
+        }
+        let single = |seed: u64| {
+            let cfg = KMeansConfig {
+                k: 4,
+                seed,
+                ..KMeansConfig::default()
+            };
+            run_kmeans(&points, &cfg).expect("kmeans").inertia
+        };
+        let cfg = KMeansConfig {
+            k: 4,
+            seed: 3,
+            n_init: 6,
+            ..KMeansConfig::default()
+        };
+        let best = run_kmeans(&points, &cfg).expect("kmeans");
+        let expected = (0..6)
+            .map(|run| single(restart_seed(3, run)))
+            .fold(f32::MAX, f32::min);
+        assert_eq!(best.inertia, expected);
+        let again = run_kmeans(&points, &cfg).expect("kmeans");
+        assert_eq!(best.centroids, again.centroids);
+    }
+
+    #[test]
+    fn trace_records_every_iteration_and_convergence() {
+        let points = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [10.0, 0.0, 0.0],
+            [11.0, 0.0, 0.0],
+        ];
+        // Already at the means: tolerance is met on the only allowed iteration
+        let settled = KMeansConfig {
+            k: 2,
+            max_iters: 1,
+            warm_start: Some(vec![[0.5, 0.0, 0.0], [10.5, 0.0, 0.0]]),
+            trace: true,
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &settled).expect("kmeans");
+        assert_eq!(result.iterations, 1);
+        assert!(result.converged);
+        let trace = result.trace.expect("trace");
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace[0].reassigned, Some(4));
+        assert_eq!(trace[0].shift, 0.0);
+
+        for assignment in [AssignmentMode::Lloyd, AssignmentMode::Hamerly] {
+            let cfg = KMeansConfig {
+                k: 2,
+                max_iters: 10,
+                tol: 0.0,
+                warm_start: Some(vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0]]),
+                assignment,
+                trace: true,
+                ..KMeansConfig::default()
+            };
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            let trace = result.trace.expect("trace");
+            assert_eq!(trace.len(), result.iterations, "{assignment:?}");
+            // [1] moves from the second cluster to the first once centroids spread
+            assert_eq!(trace[0].reassigned, Some(4), "{assignment:?}");
+            assert_eq!(trace[1].reassigned, Some(1), "{assignment:?}");
+            assert!(trace[0].inertia > trace[trace.len() - 1].inertia);
+            assert!(!result.converged, "tol = 0 can only stop at max_iters");
+        }
+        assert!(run_kmeans(
+            &points,
+            &KMeansConfig {
+                k: 2,
+                ..KMeansConfig::default()
+            }
+        )
+        .expect("kmeans")
+        .trace
+        .is_none());
+    }
+
+    #[test]
+    fn pinned_centroids_stay_put_and_come_first() {
+        let mut points = Vec::new();
+        for i in 0..50 {
+            let t = (i % 5) as f32;
+            points.push([t, 0.0, 0.0]);
+            points.push([40.0 + t, 0.0, 0.0]);
+            points.push([80.0 + t, 0.0, 0.0]);
+        }
+        let pin = [3.5, 0.0, 0.0];
+        for (assignment, mini_batch) in [
+            (AssignmentMode::Lloyd, None),
+            (AssignmentMode::Hamerly, None),
+            (AssignmentMode::Lloyd, Some(30)),
+        ] {
+            let cfg = KMeansConfig {
+                k: 3,
+                seed: 5,
+                pinned: vec![pin],
+                mini_batch,
+                assignment,
+                ..KMeansConfig::default()
+            };
+            let result = run_kmeans(&points, &cfg).expect("kmeans");
+            assert_eq!(result.centroids[0], pin, "{assignment:?} {mini_batch:?}");
+            assert_eq!(result.counts[0], 50, "{assignment:?} {mini_batch:?}");
+            let mut free: Vec<f32> = result.centroids[1..].iter().map(|c| c[0]).collect();
+            free.sort_by(f32::total_cmp);
+            assert!(
+                (free[0] - 42.0).abs() < 0.5 && (free[1] - 82.0).abs() < 0.5,
+                "{assignment:?} {mini_batch:?}: {:?}",
+                result.centroids
+            );
+        }
+
+        let too_many = KMeansConfig {
+            k: 1,
+            pinned: vec![pin, [40.0, 0.0, 0.0]],
+            ..KMeansConfig::default()
+        };
+        assert!(matches!(
+            run_kmeans(&points, &too_many),
+            Err(KMeansError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn bad_input_is_reported_not_panicked() {
+        let points = vec![[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]];
+        let cfg = |k: usize| KMeansConfig {
+            k,
+            ..KMeansConfig::default()
+        };
+        assert_eq!(
+            run_kmeans(&[], &cfg(1)).unwrap_err(),
+            KMeansError::EmptyData
+        );
+        assert_eq!(
+            run_kmeans(&points, &cfg(0)).unwrap_err(),
+            KMeansError::ZeroK
+        );
+        assert_eq!(
+            run_kmeans(&points, &cfg(3)).unwrap_err(),
+            KMeansError::KTooLarge { k: 3, points: 2 }
+        );
+        let warm = KMeansConfig {
+            warm_start: Some(vec![[0.0; 3]]),
+            ..cfg(2)
+        };
+        assert_eq!(
+            run_kmeans(&points, &warm).unwrap_err(),
+            KMeansError::WarmStartMismatch {
+                expected: 2,
+                got: 1
+            }
+        );
+        let nan = vec![[0.0, 0.0, 0.0], [1.0, f32::NAN, 1.0]];
+        assert_eq!(
+            run_kmeans(&nan, &cfg(1)).unwrap_err(),
+            KMeansError::NonFinite { index: 1 }
+        );
+        assert_eq!(
+            run_kmeans_weighted(&points, &[1.0, -2.0], &cfg(1)).unwrap_err(),
+            KMeansError::InvalidWeight { index: 1 }
+        );
+        assert_eq!(
+            run_kmeans_weighted(&points, &[1.0], &cfg(1)).unwrap_err(),
+            KMeansError::WeightsLengthMismatch {
+                points: 2,
+                weights: 1
+            }
+        );
+        let dbscan = DbscanConfig::default();
+        assert_eq!(
+            run_dbscan_weighted(&points, &[1.0, 2.0, 3.0], &dbscan).unwrap_err(),
+            KMeansError::WeightsLengthMismatch {
+                points: 2,
+                weights: 3
+            }
+        );
+        assert_eq!(
+            run_dbscan_weighted(&points, &[f32::INFINITY, 1.0], &dbscan).unwrap_err(),
+            KMeansError::InvalidWeight { index: 0 }
+        );
+        let gmm = GmmConfig {
+            kmeans: cfg(1),
+            ..GmmConfig::default()
+        };
+        assert_eq!(
+            run_gmm_weighted(&points, &[], &gmm).unwrap_err(),
+            KMeansError::WeightsLengthMismatch {
+                points: 2,
+                weights: 0
+            }
+        );
+        assert_eq!(
+            run_gmm_weighted(&points, &[1.0, f32::NAN], &gmm).unwrap_err(),
+            KMeansError::InvalidWeight { index: 1 }
+        );
+    }
+
+    #[test]
+    fn weighted_points_match_expanded_duplicates() {
+        let unique = [
+            [0.1, 0.2, 0.3],
+            [0.15, 0.2, 0.3],
+            [0.9, 0.8, 0.7],
+            [0.8, 0.8, 0.7],
+        ];
+        let weights = [30.0, 10.0, 5.0, 15.0];
+        let mut expanded = Vec::new();
+        for (p, &w) in unique.iter().zip(weights.iter()) {
+            expanded.extend(std::iter::repeat_n(*p, w as usize));
+        }
+        let cfg = KMeansConfig {
+            k: 2,
+            max_iters: 20,
+            tol: 1e-6,
+            warm_start: Some(vec![[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]]),
+            ..KMeansConfig::default()
+        };
+        let weighted = run_kmeans_weighted(&unique, &weights, &cfg).expect("kmeans");
+        let plain = run_kmeans(&expanded, &cfg).expect("kmeans");
+        assert_eq!(weighted.counts, vec![40, 20]);
+        assert_eq!(weighted.counts, plain.counts);
+        assert!((weighted.inertia - plain.inertia).abs() < 1e-4);
+        for (a, b) in weighted.centroids.iter().zip(plain.centroids.iter()) {
+            for j in 0..3 {
+                assert!((a[j] - b[j]).abs() < 1e-5);
+            }
+        }
+        // 0.1 * 30 + 0.15 * 10 over 40
+        assert!((weighted.centroids[0][0] - 0.1125).abs() < 1e-5);
+    }
+
+    #[test]
+    fn seeding_skips_zero_weight_points() {
+        let points = [[0.0, 0.0, 0.0], [5.0, 5.0, 5.0], [10.0, 0.0, 0.0]];
+        let weights = [1.0, 0.0, 1.0];
+        let dataset = PointsSoa::from_weighted_points(&points, &weights).expect("weights");
+        let metric = Metric::new(&KMeansConfig::default()).expect("metric");
+        for seed in 0..20 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            let seeds = kmeans_plus_plus(&dataset, &[], 2, &metric, &mut rng).to_vec();
+            assert!(!seeds.contains(&[5.0, 5.0, 5.0]), "seed {seed}: {seeds:?}");
+        }
+    }
+}
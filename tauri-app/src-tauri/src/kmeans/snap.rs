@@ -0,0 +1,150 @@
+//! Moving fitted centroids onto real samples, so every palette entry is a
+//! color that actually occurs in the image.
+
+use rayon::prelude::*;
+
+use super::{assignment_step, best_centroid, CentroidsSoa, KMeansResult, Metric, PointsSoa};
+
+/// Points per parallel work unit; fixed so ties resolve the same way for
+/// any thread count.
+const SNAP_CHUNK: usize = 4096;
+
+/// Replace every centroid after the first `pinned` with its nearest member
+/// sample (the nearest sample overall for a cluster without members), then
+/// recount and re-measure inertia against the snapped centroids. Samples
+/// with zero weight never occur in the image and are skipped.
+pub(super) fn snap_to_samples(
+    dataset: &PointsSoa,
+    result: &mut KMeansResult,
+    pinned: usize,
+    metric: &Metric,
+) {
+    let centroids = CentroidsSoa::from_vec(&result.centroids);
+    let k = centroids.len();
+
+    // Per cluster: (squared distance, sample index) of its nearest member
+    let chunk_nearest: Vec<Vec<(f32, usize)>> = (0..dataset.len().div_ceil(SNAP_CHUNK))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut nearest = vec![(f32::INFINITY, usize::MAX); k];
+            let end = ((chunk + 1) * SNAP_CHUNK).min(dataset.len());
+            for idx in chunk * SNAP_CHUNK..end {
+                if dataset.weight(idx) <= 0.0 {
+                    continue;
+                }
+                let (px, py, pz) = dataset.component_tuple(idx);
+                let (label, dist) = best_centroid(px, py, pz, &centroids, metric);
+                if dist < nearest[label].0 {
+                    nearest[label] = (dist, idx);
+                }
+            }
+            nearest
+        })
+        .collect();
+    let mut nearest = vec![(f32::INFINITY, usize::MAX); k];
+    for chunk in chunk_nearest {
+        for (best, candidate) in nearest.iter_mut().zip(chunk) {
+            // Strict so ties keep the earlier chunk, i.e. the lower index
+            if candidate.0 < best.0 {
+                *best = candidate;
+            }
+        }
+    }
+
+    let mut snapped = centroids.clone();
+    let mut distances = vec![0.0f32; k];
+    for cluster in pinned..k {
+        let (dist, idx) = match nearest[cluster] {
+            (_, usize::MAX) => nearest_sample(dataset, &centroids, cluster, metric),
+            found => found,
+        };
+        if idx == usize::MAX {
+            // Every sample has zero weight; leave the centroid where it is
+            continue;
+        }
+        snapped.set_from_soa(cluster, dataset, idx);
+        distances[cluster] = dist.sqrt();
+    }
+
+    let (partials, inertia, _) = assignment_step(dataset, &snapped, metric, None);
+    result.centroids = snapped.to_vec();
+    result.counts = partials.iter().map(|p| p.weight.round() as usize).collect();
+    result.inertia = inertia;
+    result.snap_distances = Some(distances);
+}
+
+/// Nearest weighted sample to centroid `cluster`, members or not.
+fn nearest_sample(
+    dataset: &PointsSoa,
+    centroids: &CentroidsSoa,
+    cluster: usize,
+    metric: &Metric,
+) -> (f32, usize) {
+    let (cx, cy, cz) = centroids.component_tuple(cluster);
+    (0..dataset.len())
+        .filter(|&idx| dataset.weight(idx) > 0.0)
+        .map(|idx| {
+            let (px, py, pz) = dataset.component_tuple(idx);
+            (metric.distance(px, py, pz, cx, cy, cz), idx)
+        })
+        .fold((f32::INFINITY, usize::MAX), |best, next| {
+            if next.0 < best.0 {
+                next
+            } else {
+                best
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kmeans::{run_kmeans, run_kmeans_weighted, KMeansConfig};
+
+    #[test]
+    fn snapped_centroids_are_samples_with_their_distance_reported() {
+        // Two blobs whose means (1.5 and 41.5) are not samples themselves
+        let mut points = Vec::new();
+        for i in 0..40 {
+            let t = (i % 4) as f32;
+            points.push([t, 0.0, 0.0]);
+            points.push([40.0 + t, 0.0, 0.0]);
+        }
+        let cfg = KMeansConfig {
+            k: 2,
+            snap_to_samples: true,
+            ..KMeansConfig::default()
+        };
+        let result = run_kmeans(&points, &cfg).expect("kmeans");
+        for centroid in &result.centroids {
+            assert!(points.contains(centroid), "{centroid:?} is not a sample");
+        }
+        assert_eq!(result.snap_distances, Some(vec![0.5, 0.5]));
+        assert_eq!(result.counts, vec![40, 40]);
+        // Ties snap to the earlier sample (1 and 41); inertia is measured
+        // against them: 10 × (1 + 0 + 1 + 4) per blob
+        let mut snapped = result.centroids.clone();
+        snapped.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        assert_eq!(snapped, vec![[1.0, 0.0, 0.0], [41.0, 0.0, 0.0]]);
+        assert!((result.inertia - 120.0).abs() < 1e-3, "{}", result.inertia);
+
+        let plain = run_kmeans(
+            &points,
+            &KMeansConfig {
+                snap_to_samples: false,
+                ..cfg.clone()
+            },
+        )
+        .expect("kmeans");
+        assert_eq!(plain.snap_distances, None);
+
+        // Zero-weight samples never become palette colors
+        let weighted = run_kmeans_weighted(
+            &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [2.0, 0.0, 0.0]],
+            &[1.0, 0.0, 1.0],
+            &KMeansConfig { k: 1, ..cfg },
+        )
+        .expect("kmeans");
+        assert_eq!(weighted.centroids, vec![[0.0, 0.0, 0.0]]);
+        assert_eq!(weighted.snap_distances, Some(vec![1.0]));
+    }
+}
@@ -0,0 +1,275 @@
+//! k-means over color plus image position, for regional (superpixel-style)
+//! palettes. Each point is five-dimensional: its three color components and
+//! its `(x, y)` place in the image scaled by [`SpatialConfig::spatial_weight`].
+//! The regular k-means loop does the fitting, with positions carried by the
+//! points, the centroids and the metric; this module seeds the run and
+//! measures the regions. The first run seeds as SLIC does, one seed per cell
+//! of a regular grid over the image; restarts use k-means++ in 5-D.
+
+use rand::rngs::SmallRng;
+
+use super::{
+    fit, kmeans_plus_plus, validate, CentroidsSoa, KMeansConfig, KMeansError, KMeansInit,
+    KMeansResult, Metric, PointsSoa, Result,
+};
+
+#[derive(Debug, Clone)]
+pub struct SpatialConfig {
+    /// Regular k-means settings. Seeding is the grid (see the module docs),
+    /// so `init` and `warm_start` are ignored, as are `pinned`, `mini_batch`
+    /// and `snap_to_samples`.
+    pub kmeans: KMeansConfig,
+    /// Color units (ΔE76 in CIELAB) that crossing the whole image is worth.
+    /// 0 clusters by color alone; larger values give compact regions.
+    pub spatial_weight: f32,
+}
+
+impl Default for SpatialConfig {
+    fn default() -> Self {
+        Self {
+            kmeans: KMeansConfig::default(),
+            spatial_weight: 40.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpatialResult {
+    /// Color centroids, counts, labels and diagnostics as for plain k-means;
+    /// inertia sums the color-plus-position distances.
+    pub kmeans: KMeansResult,
+    /// Mean member position per cluster, in the units of the input positions
+    /// (fractions of width and height for sampler output).
+    pub positions: Vec<[f32; 2]>,
+    /// `[min_x, min_y, max_x, max_y]` over each cluster's members; `None`
+    /// for a cluster that ended up empty.
+    pub bounds: Vec<Option<[f32; 4]>>,
+}
+
+/// Spatial k-means where `positions[i]` is where `points[i]` sits in the image.
+pub fn run_spatial_kmeans(
+    points: &[[f32; 3]],
+    positions: &[[f32; 2]],
+    cfg: &SpatialConfig,
+) -> Result<SpatialResult> {
+    let dataset = PointsSoa::from_points(points).with_positions(positions)?;
+    run_spatial_kmeans_soa(&dataset, cfg)
+}
+
+/// Spatial k-means over points that carry positions
+/// ([`PointsSoa::with_positions`]).
+pub fn run_spatial_kmeans_soa(dataset: &PointsSoa, cfg: &SpatialConfig) -> Result<SpatialResult> {
+    let Some(positions) = dataset.positions() else {
+        return Err(KMeansError::InvalidConfig(
+            "spatial k-means needs point positions",
+        ));
+    };
+    if !(cfg.spatial_weight >= 0.0 && cfg.spatial_weight.is_finite()) {
+        return Err(KMeansError::InvalidConfig(
+            "spatial_weight must be finite and >= 0",
+        ));
+    }
+    // Labels are always needed for the bounds
+    let kmeans = KMeansConfig {
+        warm_start: None,
+        pinned: Vec::new(),
+        mini_batch: None,
+        snap_to_samples: false,
+        return_labels: true,
+        ..cfg.kmeans.clone()
+    };
+    validate(dataset, &kmeans)?;
+    let metric = Metric {
+        spatial_scale: Some(cfg.spatial_weight * cfg.spatial_weight),
+        ..Metric::new(&kmeans)?
+    };
+
+    let (mut result, centroids) = fit(dataset, &kmeans, metric)?;
+    let labels = result.labels.take().expect("labels requested");
+    let mut bounds: Vec<Option<[f32; 4]>> = vec![None; centroids.len()];
+    for (&label, &[x, y]) in labels.iter().zip(positions) {
+        let cell = &mut bounds[label as usize];
+        *cell = Some(match *cell {
+            Some([x0, y0, x1, y1]) => [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+            None => [x, y, x, y],
+        });
+    }
+    if cfg.kmeans.return_labels {
+        result.labels = Some(labels);
+    }
+    Ok(SpatialResult {
+        kmeans: result,
+        positions: centroids.positions.expect("spatial centroids"),
+        bounds,
+    })
+}
+
+/// Starting centroids for restart `run` of a spatial fit: the grid first,
+/// then k-means++ over color and position.
+pub(super) fn seed(
+    dataset: &PointsSoa,
+    k: usize,
+    metric: &Metric,
+    run: usize,
+    rng: &mut SmallRng,
+) -> (CentroidsSoa, Option<KMeansInit>) {
+    if run > 0 {
+        let seeded = kmeans_plus_plus(dataset, &[], k, metric, rng);
+        return (seeded, Some(KMeansInit::KMeansPlusPlus));
+    }
+    (grid_seeds(dataset, k), None)
+}
+
+/// One seed per cell of a `ceil(√k)`-column grid over the unit square: the
+/// point nearest the cell centre (the earlier point on ties).
+fn grid_seeds(dataset: &PointsSoa, k: usize) -> CentroidsSoa {
+    let positions = dataset.positions().expect("spatial dataset");
+    let cols = (k as f64).sqrt().ceil() as usize;
+    let rows = k.div_ceil(cols);
+    let mut centroids = CentroidsSoa::with_len(k, dataset);
+    for cell in 0..k {
+        let target = [
+            ((cell % cols) as f32 + 0.5) / cols as f32,
+            ((cell / cols) as f32 + 0.5) / rows as f32,
+        ];
+        let nearest = (0..positions.len())
+            .min_by(|&a, &b| {
+                let da = squared_gap(positions[a], target);
+                let db = squared_gap(positions[b], target);
+                da.total_cmp(&db)
+            })
+            .expect("validated non-empty");
+        centroids.set_from_soa(cell, dataset, nearest);
+    }
+    centroids
+}
+
+#[inline]
+fn squared_gap(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let dx = a[0] - b[0];
+    let dy = a[1] - b[1];
+    dx * dx + dy * dy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kmeans::{AssignmentMode, EmptyClusterStrategy};
+
+    /// A 10×10 image as (color, position) pairs, colored by `paint(x, y)`.
+    fn image(paint: impl Fn(usize, usize) -> [f32; 3]) -> (Vec<[f32; 3]>, Vec<[f32; 2]>) {
+        let mut points = Vec::new();
+        let mut positions = Vec::new();
+        for y in 0..10 {
+            for x in 0..10 {
+                points.push(paint(x, y));
+                positions.push([(x as f32 + 0.5) / 10.0, (y as f32 + 0.5) / 10.0]);
+            }
+        }
+        (points, positions)
+    }
+
+    #[test]
+    fn one_color_splits_into_regions() {
+        let (points, positions) = image(|x, _| [50.0, (x % 2) as f32, 0.0]);
+        let cfg = SpatialConfig {
+            kmeans: KMeansConfig {
+                k: 2,
+                return_labels: true,
+                ..KMeansConfig::default()
+            },
+            ..SpatialConfig::default()
+        };
+        let result = run_spatial_kmeans(&points, &positions, &cfg).expect("spatial");
+        assert!(result.kmeans.converged);
+        assert_eq!(result.kmeans.counts, vec![50, 50]);
+        assert!((result.positions[0][0] - 0.25).abs() < 1e-5, "{result:?}");
+        assert!((result.positions[1][0] - 0.75).abs() < 1e-5, "{result:?}");
+        assert_eq!(result.bounds[0], Some([0.05, 0.05, 0.45, 0.95]));
+        assert_eq!(result.bounds[1], Some([0.55, 0.05, 0.95, 0.95]));
+        let labels = result.kmeans.labels.expect("labels");
+        assert_eq!(labels[9], 1);
+        assert_eq!(labels[90], 0);
+
+        assert_eq!(
+            run_spatial_kmeans(&points, &positions[1..], &cfg).unwrap_err(),
+            KMeansError::PositionsLengthMismatch {
+                points: 100,
+                positions: 99
+            }
+        );
+        let plain = PointsSoa::from_points(&points);
+        assert!(run_spatial_kmeans_soa(&plain, &cfg).is_err());
+    }
+
+    #[test]
+    fn zero_spatial_weight_clusters_by_color_alone() {
+        // Checkerboard: every color covers the whole image
+        let red = [50.0, 60.0, 40.0];
+        let blue = [30.0, 20.0, -50.0];
+        let (points, positions) = image(|x, y| if (x + y) % 2 == 0 { red } else { blue });
+        let cfg = SpatialConfig {
+            kmeans: KMeansConfig {
+                k: 2,
+                ..KMeansConfig::default()
+            },
+            spatial_weight: 0.0,
+        };
+        let result = run_spatial_kmeans(&points, &positions, &cfg).expect("spatial");
+        assert_eq!(result.kmeans.counts, vec![50, 50]);
+        let mut colors = result.kmeans.centroids.clone();
+        colors.sort_by(|a, b| a[2].total_cmp(&b[2]));
+        assert_eq!(colors, vec![blue, red]);
+        for bounds in &result.bounds {
+            let [x0, y0, x1, y1] = bounds.expect("non-empty");
+            assert!(x0 < 0.1 && y0 < 0.1 && x1 > 0.9 && y1 > 0.9);
+        }
+    }
+
+    #[test]
+    fn spatial_runs_take_the_regular_kmeans_options() {
+        // Left half red, right half blue, in four regions
+        let (points, positions) = image(|x, _| {
+            if x < 5 {
+                [50.0, 60.0, 40.0]
+            } else {
+                [30.0, 20.0, -50.0]
+            }
+        });
+        let lloyd = SpatialConfig {
+            kmeans: KMeansConfig {
+                k: 4,
+                trace: true,
+                ..KMeansConfig::default()
+            },
+            ..SpatialConfig::default()
+        };
+        let hamerly = SpatialConfig {
+            kmeans: KMeansConfig {
+                assignment: AssignmentMode::Hamerly,
+                ..lloyd.kmeans.clone()
+            },
+            ..lloyd.clone()
+        };
+        let a = run_spatial_kmeans(&points, &positions, &lloyd).expect("lloyd");
+        let b = run_spatial_kmeans(&points, &positions, &hamerly).expect("hamerly");
+        assert_eq!(a.kmeans.centroids, b.kmeans.centroids);
+        assert_eq!(a.positions, b.positions);
+        assert_eq!(a.kmeans.counts.iter().sum::<usize>(), 100);
+        let trace = a.kmeans.trace.expect("trace");
+        assert_eq!(trace.len(), a.kmeans.iterations);
+        assert!(a.kmeans.init.is_none());
+
+        let restarts = SpatialConfig {
+            kmeans: KMeansConfig {
+                n_init: 4,
+                on_empty: EmptyClusterStrategy::FarthestPoint,
+                ..lloyd.kmeans.clone()
+            },
+            ..lloyd
+        };
+        let best = run_spatial_kmeans(&points, &positions, &restarts).expect("restarts");
+        assert!(best.kmeans.inertia <= a.kmeans.inertia);
+        assert_eq!(best.positions.len(), best.kmeans.centroids.len());
+    }
+}
@@ -1,3 +1,7 @@
+pub mod algorithm;
+pub mod analysis;
 pub mod color;
 pub mod image_pipeline;
 pub mod kmeans;
+pub mod merge;
+pub mod quantize;
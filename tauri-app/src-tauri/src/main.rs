@@ -2,38 +2,16 @@
 
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::time::Instant;
 use tauri::AppHandle;
-use tauri_app::color;
-use tauri_app::image_pipeline::{prepare_samples, SampleParams};
-use tauri_app::kmeans::{run_kmeans, KMeansConfig};
+use tauri_app::algorithm::PaletteAlgorithm;
+use tauri_app::analysis::{self, AnalysisError, AnalysisRequest, ClusterOut};
+use tauri_app::color::{ColorSpace, DeltaE};
+use tauri_app::image_pipeline::{SampleMode, SampleParams};
+use tauri_app::kmeans::KMeansInit;
+use tauri_app::merge::MergeTree;
 use tauri_plugin_dialog;
 use tauri_plugin_shell;
 
-#[derive(Debug, Clone, Copy)]
-enum ColorSpace {
-    Rgb,
-    Hsl,
-    Yuv,
-    Cielab,
-    Cieluv,
-}
-
-impl ColorSpace {
-    fn parse(s: &str) -> Result<Self, String> {
-        match s.to_ascii_uppercase().as_str() {
-            "RGB" => Ok(Self::Rgb),
-            "HSL" => Ok(Self::Hsl),
-            "YUV" => Ok(Self::Yuv),
-            "CIELAB" | "LAB" => Ok(Self::Cielab),
-            "CIELUV" | "LUV" => Ok(Self::Cieluv),
-            other => Err(format!(
-                "Unsupported color space '{other}' (RGB|HSL|YUV|CIELAB|CIELUV)"
-            )),
-        }
-    }
-}
-
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct AnalyzeRequest {
@@ -48,7 +26,7 @@ struct AnalyzeRequest {
     min_lum: u8,
     // Accept aliases: space|color_space
     #[serde(default = "default_space", alias = "color_space")]
-    space: String,
+    space: ColorSpace,
     #[serde(default = "default_tol")]
     tol: f32,
     #[serde(default = "default_max_iters", alias = "max_iters")]
@@ -57,10 +35,43 @@ struct AnalyzeRequest {
     seed: u64,
     #[serde(default = "default_max_samples")]
     max_samples: usize,
+    // Per-axis distance weights in the clustering space, e.g. [0.5, 1, 1]
+    #[serde(default, alias = "axis_weights")]
+    axis_weights: Option<[f32; 3]>,
+    // Cluster every pixel via a color histogram with this many bits per channel (1-8)
+    #[serde(default, alias = "histogram_bits")]
+    histogram_bits: Option<u8>,
+    // kmeans | medianCut | octree | wu | dbscan
+    #[serde(default)]
+    algorithm: PaletteAlgorithm,
+    // auto | kmeans++ | kmeans|| | random | medianCut | wu
+    #[serde(default)]
+    init: KMeansInit,
+    // Hex colors kept fixed as k-means centroids, e.g. ["#D7263D"]
+    #[serde(default)]
+    pinned: Vec<String>,
+    // DBSCAN radius in clustering-space units (ΔE76 in CIELAB)
+    #[serde(default = "default_eps")]
+    eps: f32,
+    // Share of samples a DBSCAN neighbourhood needs to seed a cluster
+    #[serde(default = "default_min_share", alias = "min_share")]
+    min_share: f32,
+    // Merge clusters closer than this ΔE, e.g. 3.0
+    #[serde(default, alias = "merge_threshold")]
+    merge_threshold: Option<f32>,
+    // cie76 | ciede2000
+    #[serde(default, alias = "delta_e")]
+    delta_e: DeltaE,
+    // Move k-means centroids onto real image colors
+    #[serde(default, alias = "snap_to_samples")]
+    snap_to_samples: bool,
+    // Cluster color plus pixel position for regional palettes, e.g. 40.0
+    #[serde(default, alias = "spatial_weight")]
+    spatial_weight: Option<f32>,
 }
 
-fn default_space() -> String {
-    "CIELAB".into()
+fn default_space() -> ColorSpace {
+    ColorSpace::Cielab
 }
 fn default_tol() -> f32 {
     1e-3
@@ -74,23 +85,11 @@ fn default_seed() -> u64 {
 fn default_max_samples() -> usize {
     300_000
 }
-
-#[derive(Debug, Serialize, Clone, Copy)]
-#[serde(rename_all = "camelCase")]
-struct RgbValue {
-    r: u8,
-    g: u8,
-    b: u8,
+fn default_eps() -> f32 {
+    4.0
 }
-
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct ClusterOut {
-    count: usize,
-    share: f64,
-    centroid_space: [f32; 3],
-    rgb: RgbValue,
-    hsv: [f32; 3],
+fn default_min_share() -> f32 {
+    0.002
 }
 
 #[derive(Debug, Serialize)]
@@ -101,17 +100,21 @@ struct AnalyzeResponse {
     duration_ms: f64,
     total_samples: usize,
     variant: String,
+    // Seeding that produced the clusters; null for single-pass quantizers
+    init: Option<KMeansInit>,
+    // Samples DBSCAN left unclustered; 0 for the other algorithms
+    noise_samples: usize,
+    // Dendrogram for re-cutting the palette size; null unless mergeThreshold was set
+    merge_tree: Option<MergeTree>,
 }
 
 #[tauri::command]
-async fn analyze_image(req: AnalyzeRequest, _app: AppHandle) -> Result<AnalyzeResponse, String> {
-    if req.path.is_empty() {
-        return Err("No file selected".into());
-    }
+async fn analyze_image(
+    req: AnalyzeRequest,
+    _app: AppHandle,
+) -> Result<AnalyzeResponse, AnalysisError> {
     let k = if req.k == 0 { 16 } else { req.k };
-    let space = ColorSpace::parse(&req.space)?;
 
-    // 1) Sampling
     let sample_params = SampleParams {
         path: PathBuf::from(&req.path),
         stride: req.stride.max(1),
@@ -119,112 +122,42 @@ async fn analyze_image(req: AnalyzeRequest, _app: AppHandle) -> Result<AnalyzeRe
         max_samples: req.max_samples.max(1),
         max_dimension: Some(3200),
         seed: req.seed,
+        mode: req
+            .histogram_bits
+            .map_or(SampleMode::Reservoir, |bits| SampleMode::Histogram { bits }),
+        positions: req.spatial_weight.is_some(),
     };
-    let samples = prepare_samples(&sample_params).map_err(|e| format!("Sampling failed: {e}"))?;
-    if samples.sampled_pixels == 0 {
-        return Err("No pixels met sampling criteria (check stride/minLum)".into());
-    }
-
-    // 2) Build working dataset in requested space
-    let dataset: Vec<[f32; 3]> = match space {
-        ColorSpace::Cielab => {
-            if let Some(lab) = &samples.samples_lab {
-                lab.clone()
-            } else {
-                samples
-                    .samples
-                    .iter()
-                    .map(|&rgb| color::rgb8_to_lab(rgb))
-                    .collect()
-            }
-        }
-        ColorSpace::Rgb => samples
-            .samples
-            .iter()
-            .map(|&rgb| [rgb[0] as f32, rgb[1] as f32, rgb[2] as f32])
-            .collect(),
-        ColorSpace::Hsl => samples
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_hsl(rgb))
-            .collect(),
-        ColorSpace::Yuv => samples
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_yuv(rgb))
-            .collect(),
-        ColorSpace::Cieluv => samples
-            .samples
-            .iter()
-            .map(|&rgb| color::rgb8_to_luv(rgb))
-            .collect(),
-    };
-
-    let effective_k = k.min(dataset.len().max(1));
-
-    // 3) Run k-means
-    let cfg = KMeansConfig {
-        k: effective_k,
+    let request = AnalysisRequest {
+        space: req.space,
+        k,
         max_iters: req.max_iter as usize,
         tol: req.tol,
         seed: req.seed,
-        warm_start: None,
-        mini_batch: None,
+        axis_weights: req.axis_weights,
+        algorithm: req.algorithm,
+        init: req.init,
+        pinned: req.pinned,
+        eps: req.eps,
+        min_share: req.min_share,
+        merge_threshold: req.merge_threshold,
+        delta_e: req.delta_e,
+        snap_to_samples: req.snap_to_samples,
+        spatial_weight: req.spatial_weight,
     };
-    let start = Instant::now();
-    let result = run_kmeans(&dataset, &cfg);
-    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
-
-    // 4) Build clusters; convert centroid to RGB and HSV
-    let mut clusters: Vec<ClusterOut> = result
-        .centroids
-        .iter()
-        .zip(result.counts.iter())
-        .filter_map(|(centroid, &count)| {
-            if count == 0 {
-                return None;
-            }
-            let rgb_u8 = match space {
-                ColorSpace::Rgb => [
-                    clamp_channel(centroid[0]),
-                    clamp_channel(centroid[1]),
-                    clamp_channel(centroid[2]),
-                ],
-                ColorSpace::Hsl => color::hsl_to_rgb8(*centroid),
-                ColorSpace::Yuv => color::yuv_to_rgb8(*centroid),
-                ColorSpace::Cielab => color::lab_to_rgb8(*centroid),
-                ColorSpace::Cieluv => color::luv_to_rgb8(*centroid),
-            };
-            let rgb = RgbValue {
-                r: rgb_u8[0],
-                g: rgb_u8[1],
-                b: rgb_u8[2],
-            };
-            let hsv = color::rgb8_to_hsv(rgb_u8);
-            Some(ClusterOut {
-                count,
-                share: (count as f64) / (samples.sampled_pixels as f64),
-                centroid_space: *centroid,
-                rgb,
-                hsv,
-            })
-        })
-        .collect();
-    clusters.sort_by(|a, b| b.count.cmp(&a.count));
+    let result = analysis::analyze_image(&sample_params, &request)?;
 
     Ok(AnalyzeResponse {
-        clusters,
+        clusters: result.clusters,
         iterations: result.iterations,
-        duration_ms,
-        total_samples: samples.sampled_pixels,
+        duration_ms: result.duration_ms,
+        total_samples: result.total_samples,
         variant: "inhouse".into(),
+        init: result.init,
+        noise_samples: result.noise_samples,
+        merge_tree: result.merge_tree,
     })
 }
 
-fn clamp_channel(value: f32) -> u8 {
-    value.round().clamp(0.0, 255.0) as u8
-}
-
 #[tauri::command]
 async fn open_image_dialog(app: AppHandle) -> Result<Option<String>, String> {
     use tauri_plugin_dialog::{DialogExt, FilePath};
@@ -242,7 +175,9 @@ async fn open_image_dialog(app: AppHandle) -> Result<Option<String>, String> {
             });
             let _ = tx.send(mapped);
         });
-    let path = rx.recv().map_err(|_| String::from("dialog channel closed"))?;
+    let path = rx
+        .recv()
+        .map_err(|_| String::from("dialog channel closed"))?;
     Ok(path)
 }
 
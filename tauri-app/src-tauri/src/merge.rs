@@ -0,0 +1,359 @@
+//! Post-processing that folds near-duplicate clusters together. Clusters are
+//! merged closest pair first (centroid linkage, ΔE between the colors as
+//! displayed) and the whole merge order is kept as a tree, so a palette-size
+//! slider can re-cut it without clustering again.
+
+use serde::Serialize;
+
+use crate::color::{self, ColorSpace, DeltaE, SpaceConversion};
+use crate::kmeans::KMeansResult;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MergeConfig {
+    /// Merge while the closest pair is less than this far apart.
+    pub threshold: f32,
+    pub delta_e: DeltaE,
+    /// The first `fixed` clusters (pinned centroids) never merge with each
+    /// other and keep their centroid when they absorb a neighbour.
+    pub fixed: usize,
+}
+
+impl Default for MergeConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 3.0,
+            delta_e: DeltaE::Ciede2000,
+            fixed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeNode {
+    /// Centroid in the clustering space.
+    pub centroid: [f32; 3],
+    pub rgb: [u8; 3],
+    pub count: usize,
+    /// The two nodes merged into this one; `None` for an original cluster.
+    pub children: Option<[usize; 2]>,
+    /// ΔE between the children when they merged; 0 for original clusters.
+    pub distance: f32,
+}
+
+/// Dendrogram over the clusters of one run.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeTree {
+    /// The original clusters in input order, then one node per merge in the
+    /// order the merges happened. Merging runs until a single node is left,
+    /// or one per fixed cluster.
+    pub nodes: Vec<MergeNode>,
+    pub leaves: usize,
+}
+
+impl MergeTree {
+    /// Merges performed before the pair at least `threshold` apart. Centroid
+    /// linkage can produce a later merge that is closer than an earlier one;
+    /// the cut still stops at the first merge over the threshold.
+    pub fn merges_below(&self, threshold: f32) -> usize {
+        self.nodes[self.leaves..]
+            .iter()
+            .take_while(|node| node.distance < threshold)
+            .count()
+    }
+
+    /// Group index per original cluster after the first `merges` merges.
+    /// Groups are numbered in order of their lowest original cluster, so
+    /// fixed clusters keep their leading positions.
+    pub fn cut(&self, merges: usize) -> Vec<usize> {
+        self.cut_roots(merges).0
+    }
+
+    /// Node at the top of each group after the first `merges` merges, in the
+    /// group order of [`MergeTree::cut`].
+    pub fn roots(&self, merges: usize) -> Vec<usize> {
+        self.cut_roots(merges).1
+    }
+
+    /// [`MergeTree::cut`] plus the node at the top of each group.
+    fn cut_roots(&self, merges: usize) -> (Vec<usize>, Vec<usize>) {
+        let top = self.leaves + merges.min(self.nodes.len() - self.leaves);
+        let mut root: Vec<usize> = (0..self.leaves).collect();
+        for node in self.leaves..top {
+            let children = self.nodes[node].children.expect("merge node");
+            for r in root.iter_mut().filter(|r| children.contains(r)) {
+                *r = node;
+            }
+        }
+        let mut roots: Vec<usize> = Vec::new();
+        let groups = root
+            .iter()
+            .map(|r| match roots.iter().position(|known| known == r) {
+                Some(group) => group,
+                None => {
+                    roots.push(*r);
+                    roots.len() - 1
+                }
+            })
+            .collect();
+        (groups, roots)
+    }
+}
+
+/// Agglomeratively merge `result`'s clusters closer than `cfg.threshold`.
+/// Centroids merge as count-weighted means in `space` (circular on a hue
+/// axis) and counts add up; labels are remapped. `inertia` still describes
+/// the unmerged clustering. A snapped run (see
+/// [`KMeansResult::snap_distances`]) keeps real sample colors: each group
+/// takes its fixed or else its heaviest member's centroid instead of the mean.
+pub fn merge_clusters(
+    result: &KMeansResult,
+    space: ColorSpace,
+    cfg: &MergeConfig,
+) -> (KMeansResult, MergeTree) {
+    let tree = build_tree(&result.centroids, &result.counts, space, cfg);
+    let (assignment, roots) = tree.cut_roots(tree.merges_below(cfg.threshold));
+    let (centroids, snap_distances) = match &result.snap_distances {
+        Some(distances) => {
+            let leaves = representatives(&assignment, roots.len(), &result.counts, cfg.fixed);
+            (
+                leaves.iter().map(|&leaf| result.centroids[leaf]).collect(),
+                Some(leaves.iter().map(|&leaf| distances[leaf]).collect()),
+            )
+        }
+        None => (
+            roots.iter().map(|&r| tree.nodes[r].centroid).collect(),
+            None,
+        ),
+    };
+    let merged = KMeansResult {
+        centroids,
+        counts: roots.iter().map(|&r| tree.nodes[r].count).collect(),
+        snap_distances,
+        labels: result.labels.as_ref().map(|labels| {
+            labels
+                .iter()
+                .map(|&label| assignment[label as usize] as u32)
+                .collect()
+        }),
+        ..result.clone()
+    };
+    (merged, tree)
+}
+
+/// Original cluster standing in for each of `groups` groups: its fixed
+/// member if it has one, else the member with the largest count (the lower
+/// index on ties).
+fn representatives(
+    assignment: &[usize],
+    groups: usize,
+    counts: &[usize],
+    fixed: usize,
+) -> Vec<usize> {
+    let mut best: Vec<Option<usize>> = vec![None; groups];
+    for (leaf, &group) in assignment.iter().enumerate() {
+        let better = best[group].is_none_or(|current| {
+            current >= fixed && (leaf < fixed || counts[leaf] > counts[current])
+        });
+        if better {
+            best[group] = Some(leaf);
+        }
+    }
+    best.into_iter()
+        .map(|leaf| leaf.expect("every group has a member"))
+        .collect()
+}
+
+fn build_tree(
+    centroids: &[[f32; 3]],
+    counts: &[usize],
+    space: ColorSpace,
+    cfg: &MergeConfig,
+) -> MergeTree {
+    let leaves = centroids.len();
+    let mut nodes: Vec<MergeNode> = centroids
+        .iter()
+        .zip(counts)
+        .map(|(&centroid, &count)| MergeNode {
+            centroid,
+            rgb: space.to_rgb8(centroid),
+            count,
+            children: None,
+            distance: 0.0,
+        })
+        .collect();
+    let mut fixed: Vec<bool> = (0..leaves).map(|idx| idx < cfg.fixed).collect();
+    let mut lab: Vec<[f32; 3]> = nodes.iter().map(|n| color::rgb8_to_lab(n.rgb)).collect();
+    let mut active: Vec<usize> = (0..leaves).collect();
+    // dist[i][j] for j < i, filled as nodes appear
+    let mut dist: Vec<Vec<f32>> = Vec::with_capacity(2 * leaves);
+    for idx in 0..leaves {
+        dist.push(
+            (0..idx)
+                .map(|j| cfg.delta_e.between(lab[idx], lab[j]))
+                .collect(),
+        );
+    }
+
+    loop {
+        let mut best: Option<(f32, usize, usize)> = None;
+        for (pos, &i) in active.iter().enumerate() {
+            for &j in &active[..pos] {
+                if fixed[i] && fixed[j] {
+                    continue;
+                }
+                let (hi, lo) = (i.max(j), i.min(j));
+                let d = dist[hi][lo];
+                if best.is_none_or(|(bd, ..)| d < bd) {
+                    best = Some((d, lo, hi));
+                }
+            }
+        }
+        let Some((distance, a, b)) = best else {
+            break;
+        };
+
+        let centroid = if fixed[a] {
+            nodes[a].centroid
+        } else if fixed[b] {
+            nodes[b].centroid
+        } else {
+            weighted_mean(&nodes[a], &nodes[b], space)
+        };
+        let rgb = space.to_rgb8(centroid);
+        let id = nodes.len();
+        nodes.push(MergeNode {
+            centroid,
+            rgb,
+            count: nodes[a].count + nodes[b].count,
+            children: Some([a, b]),
+            distance,
+        });
+        fixed.push(fixed[a] || fixed[b]);
+        lab.push(color::rgb8_to_lab(rgb));
+        active.retain(|&n| n != a && n != b);
+        let mut row = vec![f32::INFINITY; id];
+        for &other in &active {
+            row[other] = cfg.delta_e.between(lab[id], lab[other]);
+        }
+        dist.push(row);
+        active.push(id);
+    }
+    MergeTree { nodes, leaves }
+}
+
+/// Count-weighted mean of two centroids, circular on the hue axis. Two empty
+/// clusters weigh the same.
+fn weighted_mean(a: &MergeNode, b: &MergeNode, space: ColorSpace) -> [f32; 3] {
+    let total = a.count + b.count;
+    let wa = if total == 0 {
+        0.5
+    } else {
+        a.count as f32 / total as f32
+    };
+    let wb = 1.0 - wa;
+    std::array::from_fn(|axis| {
+        if space.hue_axis() == Some(axis) {
+            let (sa, ca) = a.centroid[axis].to_radians().sin_cos();
+            let (sb, cb) = b.centroid[axis].to_radians().sin_cos();
+            (wa * sa + wb * sb)
+                .atan2(wa * ca + wb * cb)
+                .to_degrees()
+                .rem_euclid(360.0)
+        } else {
+            wa * a.centroid[axis] + wb * b.centroid[axis]
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(centroids: Vec<[f32; 3]>, counts: Vec<usize>) -> KMeansResult {
+        KMeansResult {
+            labels: Some((0..centroids.len() as u32).collect()),
+            centroids,
+            counts,
+            iterations: 1,
+            inertia: 0.0,
+            init: None,
+            converged: true,
+            trace: None,
+            empty_clusters: None,
+            snap_distances: None,
+        }
+    }
+
+    #[test]
+    fn near_duplicates_merge_and_the_tree_recuts() {
+        // Two reds ~1 ΔE apart, a blue, and a green
+        let run = result(
+            vec![
+                [50.0, 60.0, 40.0],
+                [30.0, -20.0, -50.0],
+                [50.5, 61.0, 40.5],
+                [70.0, -60.0, 50.0],
+            ],
+            vec![30, 40, 10, 20],
+        );
+        let (merged, tree) = merge_clusters(&run, ColorSpace::Cielab, &MergeConfig::default());
+        assert_eq!(merged.counts, vec![40, 40, 20]);
+        assert!((merged.centroids[0][0] - 50.125).abs() < 1e-4, "{merged:?}");
+        assert_eq!(merged.labels, Some(vec![0, 1, 0, 2]));
+
+        assert_eq!(tree.leaves, 4);
+        assert_eq!(tree.nodes.len(), 7);
+        assert_eq!(tree.nodes[4].children, Some([0, 2]));
+        assert_eq!(tree.nodes[6].count, 100);
+        assert_eq!(tree.cut(0), vec![0, 1, 2, 3]);
+        assert_eq!(tree.cut(3), vec![0, 0, 0, 0]);
+        assert_eq!(tree.cut(99), vec![0, 0, 0, 0]);
+
+        let strict = MergeConfig {
+            threshold: 0.5,
+            ..MergeConfig::default()
+        };
+        assert_eq!(
+            merge_clusters(&run, ColorSpace::Cielab, &strict)
+                .0
+                .counts
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn fixed_clusters_absorb_without_moving_or_meeting() {
+        let run = result(
+            vec![[50.0, 60.0, 40.0], [50.0, 61.0, 40.0], [50.5, 60.5, 40.0]],
+            vec![0, 5, 50],
+        );
+        let cfg = MergeConfig {
+            threshold: 10.0,
+            fixed: 2,
+            ..MergeConfig::default()
+        };
+        let (merged, tree) = merge_clusters(&run, ColorSpace::Cielab, &cfg);
+        // The two pins stay apart; the free cluster joins the closer one
+        assert_eq!(merged.centroids.len(), 2);
+        assert_eq!(&merged.centroids[..2], &run.centroids[..2]);
+        assert_eq!(merged.counts.iter().sum::<usize>(), 55);
+        assert_eq!(tree.nodes.len(), 4);
+    }
+
+    #[test]
+    fn snapped_runs_keep_a_member_color_when_merging() {
+        let mut run = result(
+            vec![[50.0, 60.0, 40.0], [30.0, -20.0, -50.0], [50.5, 61.0, 40.5]],
+            vec![10, 40, 30],
+        );
+        run.snap_distances = Some(vec![0.5, 1.0, 2.0]);
+        let (merged, _) = merge_clusters(&run, ColorSpace::Cielab, &MergeConfig::default());
+        // The reds merge onto the heavier one instead of their mean
+        assert_eq!(merged.counts, vec![40, 40]);
+        assert_eq!(merged.centroids[0], run.centroids[2]);
+        assert_eq!(merged.snap_distances, Some(vec![2.0, 1.0]));
+    }
+}
@@ -0,0 +1,104 @@
+use super::{summarize, validate, weight_of, Quantizer};
+use crate::kmeans::{KMeansResult, Result};
+
+/// Heckbert's median cut: repeatedly split the box with the largest
+/// extent × weight along its widest axis at the weighted median.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MedianCut;
+
+impl Quantizer for MedianCut {
+    fn quantize(
+        &self,
+        points: &[[f32; 3]],
+        weights: Option<&[f32]>,
+        k: usize,
+    ) -> Result<KMeansResult> {
+        validate(points, weights, k)?;
+
+        let mut boxes: Vec<Vec<usize>> = vec![(0..points.len()).collect()];
+        while boxes.len() < k {
+            let mut best: Option<(usize, usize, f64)> = None;
+            for (idx, members) in boxes.iter().enumerate() {
+                let (axis, extent) = widest_axis(points, members);
+                if extent <= 0.0 {
+                    continue;
+                }
+                let weight: f64 = members.iter().map(|&i| weight_of(weights, i) as f64).sum();
+                let priority = extent as f64 * weight;
+                if best.is_none_or(|(_, _, p)| priority > p) {
+                    best = Some((idx, axis, priority));
+                }
+            }
+            let Some((idx, axis, _)) = best else {
+                // Every box is a single repeated color
+                break;
+            };
+
+            let mut members = std::mem::take(&mut boxes[idx]);
+            members.sort_by(|&a, &b| points[a][axis].total_cmp(&points[b][axis]).then(a.cmp(&b)));
+            let cut = median_cut_position(points, weights, &members, axis);
+            let upper = members.split_off(cut);
+            boxes[idx] = members;
+            boxes.push(upper);
+        }
+
+        let mut labels = vec![0u32; points.len()];
+        for (label, members) in boxes.iter().enumerate() {
+            for &i in members {
+                labels[i] = label as u32;
+            }
+        }
+        Ok(summarize(points, weights, &labels, boxes.len()))
+    }
+}
+
+fn widest_axis(points: &[[f32; 3]], members: &[usize]) -> (usize, f32) {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for &i in members {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(points[i][axis]);
+            max[axis] = max[axis].max(points[i][axis]);
+        }
+    }
+    (0..3)
+        .map(|axis| (axis, max[axis] - min[axis]))
+        .fold(
+            (0, f32::MIN),
+            |best, cur| if cur.1 > best.1 { cur } else { best },
+        )
+}
+
+/// First index of the upper half: the weighted median of `members` (sorted
+/// along `axis`), nudged so equal values never straddle the cut.
+fn median_cut_position(
+    points: &[[f32; 3]],
+    weights: Option<&[f32]>,
+    members: &[usize],
+    axis: usize,
+) -> usize {
+    let total: f64 = members.iter().map(|&i| weight_of(weights, i) as f64).sum();
+    let mut acc = 0.0;
+    let mut cut = members.len() / 2;
+    for (pos, &i) in members.iter().enumerate() {
+        acc += weight_of(weights, i) as f64;
+        if acc * 2.0 >= total {
+            cut = pos + 1;
+            break;
+        }
+    }
+    let value = |pos: usize| points[members[pos]][axis];
+    let mut forward = cut.clamp(1, members.len() - 1);
+    while forward < members.len() && value(forward) == value(forward - 1) {
+        forward += 1;
+    }
+    if forward < members.len() {
+        return forward;
+    }
+    // The median sits in the top run of equal values: cut just below that run
+    let mut backward = members.len() - 1;
+    while value(backward) == value(backward - 1) {
+        backward -= 1;
+    }
+    backward
+}
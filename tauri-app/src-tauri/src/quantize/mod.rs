@@ -0,0 +1,266 @@
+//! Deterministic, single-pass alternatives to k-means for posterization and
+//! GIF-style palettes. Every quantizer partitions points in whatever space the
+//! caller clusters in and reports the result as a [`KMeansResult`], so the
+//! rest of the pipeline (cluster building, sorting, UI) does not care which
+//! algorithm ran. All axes are treated linearly: hue wrap-around is not
+//! special-cased, so prefer non-hue spaces with these.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::kmeans::{KMeansError, KMeansResult, Result};
+
+mod median_cut;
+mod octree;
+mod wu;
+
+pub use median_cut::MedianCut;
+pub use octree::Octree;
+pub use wu::Wu;
+
+pub trait Quantizer {
+    /// Partition `points` into at most `k` groups. `weights[i]` counts
+    /// `points[i]` that many times (e.g. histogram pixel counts).
+    fn quantize(
+        &self,
+        points: &[[f32; 3]],
+        weights: Option<&[f32]>,
+        k: usize,
+    ) -> Result<KMeansResult>;
+}
+
+/// Single-pass quantizer selector; see
+/// [`PaletteAlgorithm`](crate::algorithm::PaletteAlgorithm) for requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuantizerKind {
+    MedianCut,
+    Octree,
+    Wu,
+}
+
+impl QuantizerKind {
+    pub const ALL: [QuantizerKind; 3] = [
+        QuantizerKind::MedianCut,
+        QuantizerKind::Octree,
+        QuantizerKind::Wu,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QuantizerKind::MedianCut => "medianCut",
+            QuantizerKind::Octree => "octree",
+            QuantizerKind::Wu => "wu",
+        }
+    }
+
+    pub fn quantizer(self) -> &'static dyn Quantizer {
+        match self {
+            QuantizerKind::MedianCut => &MedianCut,
+            QuantizerKind::Octree => &Octree,
+            QuantizerKind::Wu => &Wu,
+        }
+    }
+}
+
+impl fmt::Display for QuantizerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for QuantizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let wanted = s.trim().replace(['-', '_'], "").to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().to_ascii_lowercase() == wanted)
+            .ok_or_else(|| format!("unknown quantizer '{s}' (expected median-cut, octree or wu)"))
+    }
+}
+
+fn validate(points: &[[f32; 3]], weights: Option<&[f32]>, k: usize) -> Result<()> {
+    if points.is_empty() {
+        return Err(KMeansError::EmptyData);
+    }
+    if k == 0 {
+        return Err(KMeansError::ZeroK);
+    }
+    if let Some(index) = points.iter().position(|p| p.iter().any(|v| !v.is_finite())) {
+        return Err(KMeansError::NonFinite { index });
+    }
+    if let Some(weights) = weights {
+        if weights.len() != points.len() {
+            return Err(KMeansError::WeightsLengthMismatch {
+                points: points.len(),
+                weights: weights.len(),
+            });
+        }
+        if let Some(index) = weights.iter().position(|w| !w.is_finite() || *w < 0.0) {
+            return Err(KMeansError::InvalidWeight { index });
+        }
+    }
+    Ok(())
+}
+
+#[inline]
+fn weight_of(weights: Option<&[f32]>, idx: usize) -> f32 {
+    weights.map_or(1.0, |w| w[idx])
+}
+
+/// Weighted means, counts and inertia for a finished partition.
+fn summarize(
+    points: &[[f32; 3]],
+    weights: Option<&[f32]>,
+    labels: &[u32],
+    groups: usize,
+) -> KMeansResult {
+    let mut sums = vec![[0.0f64; 3]; groups];
+    let mut totals = vec![0.0f64; groups];
+    for (idx, (p, &label)) in points.iter().zip(labels).enumerate() {
+        let w = weight_of(weights, idx) as f64;
+        let sum = &mut sums[label as usize];
+        for axis in 0..3 {
+            sum[axis] += w * p[axis] as f64;
+        }
+        totals[label as usize] += w;
+    }
+    let centroids: Vec<[f32; 3]> = sums
+        .iter()
+        .zip(&totals)
+        .map(|(sum, &total)| {
+            let inv = if total > 0.0 { 1.0 / total } else { 0.0 };
+            sum.map(|s| (s * inv) as f32)
+        })
+        .collect();
+
+    let mut inertia = 0.0f64;
+    for (idx, (p, &label)) in points.iter().zip(labels).enumerate() {
+        let c = centroids[label as usize];
+        let d: f32 = (0..3).map(|axis| (p[axis] - c[axis]).powi(2)).sum();
+        inertia += weight_of(weights, idx) as f64 * d as f64;
+    }
+
+    KMeansResult {
+        centroids,
+        counts: totals.iter().map(|t| t.round() as usize).collect(),
+        iterations: 0,
+        inertia: inertia as f32,
+        labels: Some(labels.to_vec()),
+        init: None,
+        converged: true,
+        trace: None,
+        empty_clusters: None,
+        snap_distances: None,
+    }
+}
+
+/// Axis-aligned bounds mapping each axis onto `0..cells` integer bins.
+struct Grid {
+    min: [f32; 3],
+    scale: [f32; 3],
+    cells: u32,
+}
+
+impl Grid {
+    fn new(points: &[[f32; 3]], cells: u32) -> Self {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in points {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(p[axis]);
+                max[axis] = max[axis].max(p[axis]);
+            }
+        }
+        let scale = std::array::from_fn(|axis| {
+            let span = max[axis] - min[axis];
+            if span > 0.0 {
+                cells as f32 / span
+            } else {
+                0.0
+            }
+        });
+        Self { min, scale, cells }
+    }
+
+    #[inline]
+    fn bin(&self, p: &[f32; 3]) -> [u32; 3] {
+        std::array::from_fn(|axis| {
+            let t = ((p[axis] - self.min[axis]) * self.scale[axis]) as u32;
+            t.min(self.cells - 1)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn three_tones() -> (Vec<[f32; 3]>, Vec<f32>) {
+        let mut points = Vec::new();
+        let mut weights = Vec::new();
+        // Equal total weight per tone, spread over different point counts
+        for i in 0..60 {
+            let t = (i % 5) as f32 * 0.5;
+            if i < 30 {
+                points.push([10.0 + t, 60.0, 20.0]);
+                weights.push(2.0);
+            }
+            points.push([80.0, 10.0 + t, 40.0]);
+            weights.push(1.0);
+            if i < 20 {
+                points.push([40.0, 90.0, 90.0 - t]);
+                weights.push(3.0);
+            }
+        }
+        (points, weights)
+    }
+
+    #[test]
+    fn every_quantizer_separates_three_tones() {
+        let (points, weights) = three_tones();
+        for kind in QuantizerKind::ALL {
+            let quantizer = kind.quantizer();
+            let result = quantizer
+                .quantize(&points, Some(&weights), 3)
+                .expect("quantize");
+            let mut counts = result.counts.clone();
+            counts.sort_unstable();
+            assert_eq!(counts, vec![60, 60, 60], "{kind}");
+            assert!(result.inertia < 100.0, "{kind}: inertia {}", result.inertia);
+
+            let again = quantizer.quantize(&points, Some(&weights), 3).unwrap();
+            assert_eq!(
+                result.centroids, again.centroids,
+                "{kind} is not deterministic"
+            );
+        }
+    }
+
+    #[test]
+    fn quantizers_never_exceed_k_or_invent_groups() {
+        let points = vec![[1.0, 2.0, 3.0]; 10];
+        for kind in QuantizerKind::ALL {
+            let result = kind.quantizer().quantize(&points, None, 4).unwrap();
+            assert_eq!(result.centroids, vec![[1.0, 2.0, 3.0]], "{kind}");
+            assert_eq!(result.counts, vec![10]);
+            assert_eq!(
+                kind.quantizer().quantize(&[], None, 4).unwrap_err(),
+                KMeansError::EmptyData
+            );
+        }
+    }
+
+    #[test]
+    fn kind_parses_cli_and_wire_names() {
+        assert_eq!("median-cut".parse(), Ok(QuantizerKind::MedianCut));
+        assert_eq!("WU".parse(), Ok(QuantizerKind::Wu));
+        assert!("kmeans".parse::<QuantizerKind>().is_err());
+        let kind: QuantizerKind = serde_json::from_str("\"medianCut\"").unwrap();
+        assert_eq!(kind, QuantizerKind::MedianCut);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use super::{summarize, validate, weight_of, Grid, Quantizer};
+use crate::kmeans::{KMeansResult, Result};
+
+/// Octree depth; the bounding box is split into `2^DEPTH` bins per axis.
+const DEPTH: u32 = 6;
+
+/// Gervautz–Purgathofer octree: bin points into a depth-6 octree over their
+/// bounding box, then fold the lightest sibling groups into their parent,
+/// one level at a time, until at most `k` leaves remain.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Octree;
+
+struct Leaf {
+    /// Path from the root, three bits per level.
+    prefix: u32,
+    weight: f64,
+    cells: Vec<usize>,
+}
+
+impl Quantizer for Octree {
+    fn quantize(
+        &self,
+        points: &[[f32; 3]],
+        weights: Option<&[f32]>,
+        k: usize,
+    ) -> Result<KMeansResult> {
+        validate(points, weights, k)?;
+
+        let grid = Grid::new(points, 1 << DEPTH);
+        let mut cell_of_key: BTreeMap<u32, usize> = BTreeMap::new();
+        let mut cell_weight: Vec<f64> = Vec::new();
+        let point_cells: Vec<usize> = points
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                let key = octree_key(grid.bin(p));
+                let next = cell_weight.len();
+                let cell = *cell_of_key.entry(key).or_insert(next);
+                if cell == next {
+                    cell_weight.push(0.0);
+                }
+                cell_weight[cell] += weight_of(weights, idx) as f64;
+                cell
+            })
+            .collect();
+
+        let mut leaves: Vec<Leaf> = cell_of_key
+            .iter()
+            .map(|(&key, &cell)| Leaf {
+                prefix: key,
+                weight: cell_weight[cell],
+                cells: vec![cell],
+            })
+            .collect();
+
+        for _ in 0..DEPTH {
+            if leaves.len() <= k {
+                break;
+            }
+            leaves = reduce_level(leaves, k);
+        }
+
+        let mut leaf_of_cell = vec![0u32; cell_weight.len()];
+        for (label, leaf) in leaves.iter().enumerate() {
+            for &cell in &leaf.cells {
+                leaf_of_cell[cell] = label as u32;
+            }
+        }
+        let labels: Vec<u32> = point_cells.iter().map(|&c| leaf_of_cell[c]).collect();
+        Ok(summarize(points, weights, &labels, leaves.len()))
+    }
+}
+
+/// Merge sibling groups (lightest first) into their parent until at most `k`
+/// leaves remain. If every group had to be visited, all leaves move up a level
+/// so the next pass again sees siblings at a common depth.
+fn reduce_level(leaves: Vec<Leaf>, k: usize) -> Vec<Leaf> {
+    let mut groups: BTreeMap<u32, Vec<Leaf>> = BTreeMap::new();
+    for leaf in leaves {
+        groups.entry(leaf.prefix >> 3).or_default().push(leaf);
+    }
+
+    let mut order: Vec<(f64, u32)> = groups
+        .iter()
+        .filter(|(_, children)| children.len() > 1)
+        .map(|(&parent, children)| (children.iter().map(|c| c.weight).sum(), parent))
+        .collect();
+    order.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    let mut remaining: usize = groups.values().map(Vec::len).sum();
+    let mut merge = Vec::new();
+    for (_, parent) in order {
+        if remaining <= k {
+            break;
+        }
+        remaining -= groups[&parent].len() - 1;
+        merge.push(parent);
+    }
+    let finished = remaining <= k;
+
+    let mut next = Vec::with_capacity(remaining);
+    for (parent, children) in groups {
+        if finished && !merge.contains(&parent) {
+            next.extend(children);
+            continue;
+        }
+        next.push(Leaf {
+            prefix: parent,
+            weight: children.iter().map(|c| c.weight).sum(),
+            cells: children.into_iter().flat_map(|c| c.cells).collect(),
+        });
+    }
+    next
+}
+
+/// Interleave the bin bits most-significant first so each octree level adds
+/// one 3-bit child index.
+fn octree_key(bin: [u32; 3]) -> u32 {
+    let mut key = 0;
+    for bit in (0..DEPTH).rev() {
+        let child = ((bin[0] >> bit) & 1) << 2 | ((bin[1] >> bit) & 1) << 1 | ((bin[2] >> bit) & 1);
+        key = key << 3 | child;
+    }
+    key
+}
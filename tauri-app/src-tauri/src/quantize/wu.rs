@@ -0,0 +1,210 @@
+use super::{summarize, validate, weight_of, Grid, Quantizer};
+use crate::kmeans::{KMeansResult, Result};
+
+/// Bins per axis; the moment tables carry one extra zero plane per axis.
+const BINS: u32 = 32;
+const SIDE: usize = BINS as usize + 1;
+
+/// Wu's greedy orthogonal bipartition: cumulative moment tables over a
+/// 32³ grid let every candidate cut be scored in O(1), and the box with the
+/// largest variance is always split where it reduces variance the most.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Wu;
+
+/// Weight, first moments and squared-norm moment of a set of points.
+#[derive(Debug, Clone, Copy, Default)]
+struct Moments {
+    weight: f64,
+    sum: [f64; 3],
+    sq: f64,
+}
+
+impl Moments {
+    fn add(&mut self, other: &Moments, sign: f64) {
+        self.weight += sign * other.weight;
+        for axis in 0..3 {
+            self.sum[axis] += sign * other.sum[axis];
+        }
+        self.sq += sign * other.sq;
+    }
+
+    fn sub(mut self, other: &Moments) -> Moments {
+        self.add(other, -1.0);
+        self
+    }
+
+    /// `|sum|² / weight`; maximising this over a split minimises variance.
+    fn spread(&self) -> f64 {
+        if self.weight <= 0.0 {
+            return 0.0;
+        }
+        self.sum.iter().map(|s| s * s).sum::<f64>() / self.weight
+    }
+
+    fn variance(&self) -> f64 {
+        self.sq - self.spread()
+    }
+}
+
+/// Half-open box in table coordinates: `lo` is exclusive, `hi` inclusive.
+#[derive(Debug, Clone, Copy)]
+struct Cube {
+    lo: [usize; 3],
+    hi: [usize; 3],
+}
+
+impl Cube {
+    fn contains(&self, cell: [usize; 3]) -> bool {
+        (0..3).all(|axis| cell[axis] > self.lo[axis] && cell[axis] <= self.hi[axis])
+    }
+}
+
+struct Table {
+    cells: Vec<Moments>,
+}
+
+impl Table {
+    fn index(cell: [usize; 3]) -> usize {
+        (cell[0] * SIDE + cell[1]) * SIDE + cell[2]
+    }
+
+    fn build(points: &[[f32; 3]], weights: Option<&[f32]>, grid: &Grid) -> (Self, Vec<[usize; 3]>) {
+        let mut cells = vec![Moments::default(); SIDE * SIDE * SIDE];
+        let point_cells: Vec<[usize; 3]> = points
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                let cell = grid.bin(p).map(|b| b as usize + 1);
+                let w = weight_of(weights, idx) as f64;
+                let m = &mut cells[Self::index(cell)];
+                m.weight += w;
+                for (sum, &v) in m.sum.iter_mut().zip(p) {
+                    *sum += w * v as f64;
+                }
+                m.sq += w * p.iter().map(|&v| (v as f64) * (v as f64)).sum::<f64>();
+                cell
+            })
+            .collect();
+
+        // Prefix sums along each axis in turn
+        for axis in 0..3 {
+            for a in 1..SIDE {
+                for b in 1..SIDE {
+                    for c in 1..SIDE {
+                        let mut cell = [a, b, c];
+                        cell[axis] -= 1;
+                        let prev = cells[Self::index(cell)];
+                        cells[Self::index([a, b, c])].add(&prev, 1.0);
+                    }
+                }
+            }
+        }
+        (Self { cells }, point_cells)
+    }
+
+    fn at(&self, cell: [usize; 3]) -> &Moments {
+        &self.cells[Self::index(cell)]
+    }
+
+    /// Moments of everything inside `cube`, by inclusion–exclusion.
+    fn volume(&self, cube: &Cube) -> Moments {
+        let mut total = Moments::default();
+        for corner in 0..8 {
+            let cell: [usize; 3] = std::array::from_fn(|axis| {
+                if corner >> axis & 1 == 1 {
+                    cube.lo[axis]
+                } else {
+                    cube.hi[axis]
+                }
+            });
+            let sign = if (corner as u32).count_ones().is_multiple_of(2) {
+                1.0
+            } else {
+                -1.0
+            };
+            total.add(self.at(cell), sign);
+        }
+        total
+    }
+
+    /// Best place to cut `cube` across `axis`: the position whose two halves
+    /// have the largest combined spread, or `None` if no cut leaves both
+    /// halves non-empty.
+    fn best_cut(&self, cube: &Cube, axis: usize, whole: &Moments) -> Option<(usize, f64)> {
+        let mut best: Option<(usize, f64)> = None;
+        for pos in cube.lo[axis] + 1..cube.hi[axis] {
+            let mut lower = *cube;
+            lower.hi[axis] = pos;
+            let below = self.volume(&lower);
+            let above = whole.sub(&below);
+            if below.weight <= 0.0 || above.weight <= 0.0 {
+                continue;
+            }
+            let score = below.spread() + above.spread();
+            if best.is_none_or(|(_, s)| score > s) {
+                best = Some((pos, score));
+            }
+        }
+        best
+    }
+}
+
+impl Quantizer for Wu {
+    fn quantize(
+        &self,
+        points: &[[f32; 3]],
+        weights: Option<&[f32]>,
+        k: usize,
+    ) -> Result<KMeansResult> {
+        validate(points, weights, k)?;
+
+        let grid = Grid::new(points, BINS);
+        let (table, point_cells) = Table::build(points, weights, &grid);
+
+        let mut cubes = vec![Cube {
+            lo: [0; 3],
+            hi: [SIDE - 1; 3],
+        }];
+        let mut variance = vec![table.volume(&cubes[0]).variance()];
+        while cubes.len() < k {
+            // Split the box with the largest variance that can still be cut
+            let mut order: Vec<usize> = (0..cubes.len()).filter(|&i| variance[i] > 0.0).collect();
+            order.sort_by(|&a, &b| variance[b].total_cmp(&variance[a]).then(a.cmp(&b)));
+            let split = order.into_iter().find_map(|idx| {
+                let whole = table.volume(&cubes[idx]);
+                (0..3)
+                    .filter_map(|axis| table.best_cut(&cubes[idx], axis, &whole).map(|c| (axis, c)))
+                    .fold(
+                        None,
+                        |best: Option<(usize, (usize, f64))>, cur| match best {
+                            Some(b) if b.1 .1 >= cur.1 .1 => Some(b),
+                            _ => Some(cur),
+                        },
+                    )
+                    .map(|(axis, (pos, _))| (idx, axis, pos))
+            });
+            let Some((idx, axis, pos)) = split else {
+                // Every remaining box is a single grid cell
+                break;
+            };
+
+            let mut upper = cubes[idx];
+            upper.lo[axis] = pos;
+            cubes[idx].hi[axis] = pos;
+            variance[idx] = table.volume(&cubes[idx]).variance();
+            variance.push(table.volume(&upper).variance());
+            cubes.push(upper);
+        }
+
+        let labels: Vec<u32> = point_cells
+            .iter()
+            .map(|&cell| {
+                cubes
+                    .iter()
+                    .position(|cube| cube.contains(cell))
+                    .expect("cubes tile the grid") as u32
+            })
+            .collect();
+        Ok(summarize(points, weights, &labels, cubes.len()))
+    }
+}
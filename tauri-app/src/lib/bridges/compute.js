@@ -32,7 +32,9 @@ const tauriClusterSchema = z
         b: finiteNumberSchema
     })
         .refine((value) => value.r >= 0 && value.r <= 255 && value.g >= 0 && value.g <= 255 && value.b >= 0 && value.b <= 255, { message: 'rgb components must be within 0-255' }),
-    hsv: z.any()
+    hsv: z.any(),
+    lch: z.any(),
+    oklch: z.any()
 })
     .transform((data, ctx) => {
     const sourceCentroid = data.centroidSpace ?? data.centroid_space;
@@ -44,6 +46,14 @@ const tauriClusterSchema = z
     if (!hsvTriple) {
         ctx.addIssue({ code: z.ZodIssueCode.custom, path: ['hsv'], message: 'hsv must contain three finite numbers' });
     }
+    const lchTriple = coerceTriple(data.lch);
+    if (!lchTriple) {
+        ctx.addIssue({ code: z.ZodIssueCode.custom, path: ['lch'], message: 'lch must contain three finite numbers' });
+    }
+    const oklchTriple = coerceTriple(data.oklch);
+    if (!oklchTriple) {
+        ctx.addIssue({ code: z.ZodIssueCode.custom, path: ['oklch'], message: 'oklch must contain three finite numbers' });
+    }
     if (ctx.issues.length > 0) {
         return z.NEVER;
     }
@@ -52,7 +62,9 @@ const tauriClusterSchema = z
         share: data.share,
         centroidSpace: centroid,
         rgb: data.rgb,
-        hsv: hsvTriple
+        hsv: hsvTriple,
+        lch: lchTriple,
+        oklch: oklchTriple
     };
 });
 const tauriComputeResponseSchema = z
@@ -98,7 +110,9 @@ function normalizeTauriCluster(raw) {
                 b: Number(rgb.b)
             }
             : undefined,
-        hsv: cluster.hsv
+        hsv: cluster.hsv,
+        lch: cluster.lch,
+        oklch: cluster.oklch
     };
 }
 function normalizeTauriResponse(raw) {
@@ -175,7 +189,9 @@ function createTauriComputeBridge() {
                 share: cluster.share,
                 centroidSpace: cluster.centroidSpace,
                 rgb: cluster.rgb,
-                hsv: cluster.hsv
+                hsv: cluster.hsv,
+                lch: cluster.lch,
+                oklch: cluster.oklch
             }));
             return {
                 clusters,
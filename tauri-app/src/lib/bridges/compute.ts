@@ -20,7 +20,13 @@ export interface ComputeBridge {
   analyze(dataset: ImageDataset, params: AnalyzeOptions): Promise<AnalysisResult>;
 }
 
-type TauriComputeErrorCode = 'missing-path' | 'invoke-failed' | 'invalid-response';
+type TauriComputeErrorCode = 'missing-path' | 'invoke-failed' | 'invalid-response' | 'analysis-failed';
+
+// Shape of `AnalysisError` as serialized by the native `analyze_image` command.
+const nativeAnalysisErrorSchema = z.object({
+  kind: z.string(),
+  message: z.string()
+});
 
 export class TauriComputeError extends Error {
   readonly code: TauriComputeErrorCode;
@@ -57,7 +63,9 @@ const tauriClusterSchema = z
         (value) => value.r >= 0 && value.r <= 255 && value.g >= 0 && value.g <= 255 && value.b >= 0 && value.b <= 255,
         { message: 'rgb components must be within 0-255' }
       ),
-    hsv: z.any()
+    hsv: z.any(),
+    lch: z.any(),
+    oklch: z.any()
   })
   .transform((data, ctx) => {
     const sourceCentroid = data.centroidSpace ?? data.centroid_space;
@@ -69,6 +77,14 @@ const tauriClusterSchema = z
     if (!hsvTriple) {
       ctx.addIssue({ code: z.ZodIssueCode.custom, path: ['hsv'], message: 'hsv must contain three finite numbers' });
     }
+    const lchTriple = coerceTriple(data.lch);
+    if (!lchTriple) {
+      ctx.addIssue({ code: z.ZodIssueCode.custom, path: ['lch'], message: 'lch must contain three finite numbers' });
+    }
+    const oklchTriple = coerceTriple(data.oklch);
+    if (!oklchTriple) {
+      ctx.addIssue({ code: z.ZodIssueCode.custom, path: ['oklch'], message: 'oklch must contain three finite numbers' });
+    }
     if (ctx.issues.length > 0) {
       return z.NEVER;
     }
@@ -77,7 +93,9 @@ const tauriClusterSchema = z
       share: data.share,
       centroidSpace: centroid as [number, number, number],
       rgb: data.rgb,
-      hsv: hsvTriple as [number, number, number]
+      hsv: hsvTriple as [number, number, number],
+      lch: lchTriple as [number, number, number],
+      oklch: oklchTriple as [number, number, number]
     };
   });
 
@@ -130,7 +148,9 @@ function normalizeTauriCluster(raw: unknown): Record<string, unknown> {
           b: Number(rgb.b)
         }
       : undefined,
-    hsv: cluster.hsv
+    hsv: cluster.hsv,
+    lch: cluster.lch,
+    oklch: cluster.oklch
   };
 }
 
@@ -200,6 +220,10 @@ function createTauriComputeBridge(): ComputeBridge | null {
       try {
         rawResponse = await tauriInvoke('analyze_image', { req });
       } catch (err) {
+        const native = nativeAnalysisErrorSchema.safeParse(err);
+        if (native.success) {
+          throw new TauriComputeError('analysis-failed', native.data.message, { cause: err });
+        }
         const message = err instanceof Error ? err.message : String(err);
         throw new TauriComputeError('invoke-failed', `Tauri analyze_image invoke failed: ${message}`, { cause: err });
       }
@@ -210,7 +234,9 @@ function createTauriComputeBridge(): ComputeBridge | null {
         share: cluster.share,
         centroidSpace: cluster.centroidSpace,
         rgb: cluster.rgb,
-        hsv: cluster.hsv
+        hsv: cluster.hsv,
+        lch: cluster.lch,
+        oklch: cluster.oklch
       })) as AnalysisResult['clusters'];
 
       return {
@@ -4,7 +4,7 @@ import type { ImageDataset } from '../compute/image-loader';
 export type View = 'home' | 'graphs' | 'exports';
 
 export interface AnalysisParams {
-  colorSpace: 'RGB' | 'HSL' | 'YUV' | 'CIELAB' | 'CIELUV';
+  colorSpace: 'RGB' | 'HSL' | 'YUV' | 'CIELAB' | 'CIELUV' | 'LCHAB' | 'LCHUV' | 'OKLAB' | 'OKLCH';
   clusters: number;
   stride: number;
   minLum: number;
@@ -43,6 +43,8 @@ export interface AnalysisCluster {
   centroidSpace: [number, number, number];
   rgb: { r: number; g: number; b: number };
   hsv: [number, number, number];
+  lch: [number, number, number];
+  oklch: [number, number, number];
 }
 
 export interface AnalysisResult {